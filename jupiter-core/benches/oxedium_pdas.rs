@@ -0,0 +1,127 @@
+use std::hint::black_box;
+
+use anchor_lang::{system_program, ToAccountMetas};
+use criterion::{criterion_group, criterion_main, Criterion};
+use jupiter_amm_interface::{ClockRef, SwapParams};
+use jupiter_core::{
+    amm::{Amm, AmmContext, SwapMode},
+    amms::oxedium_amm::{find_treasury_pda, find_vault_pda, OxediumAmm},
+    constants::USDC_MINT,
+    test_harness::{
+        load_accounts_snapshot, refresh_pyth_price_updates, snapshot_directory_name,
+        AmmTestAccountsSnapshot,
+    },
+};
+use program_interfaces::jupiter_dex_interfaces::client::accounts::OxediumSwap;
+use solana_sdk::instruction::AccountMeta;
+use solana_sdk::pubkey;
+use solana_sdk::pubkey::Pubkey;
+use spl_associated_token_account::get_associated_token_address_with_program_id;
+
+const OXEDIUM_POOL: Pubkey = pubkey!("DZzt6k2QN77Khj4hYBZFyJVjzuV3KxkSqjMFaUvQoxz1");
+const JUPITER_PROGRAM_ID: Pubkey = pubkey!("JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4");
+const SOL_MINT: Pubkey = spl_token::native_mint::ID;
+
+fn load_oxedium_amm() -> OxediumAmm {
+    let amm_test_accounts_snapshot = AmmTestAccountsSnapshot::load(OXEDIUM_POOL, None);
+    let clock = amm_test_accounts_snapshot.get_clock().unwrap();
+    let keyed_account = amm_test_accounts_snapshot.get_keyed_account().unwrap();
+    let amm_context = AmmContext {
+        clock_ref: ClockRef::from(clock.clone()),
+    };
+    let mut amm = OxediumAmm::from_keyed_account(&keyed_account, &amm_context).unwrap();
+
+    let mut account_map = load_accounts_snapshot(&snapshot_directory_name(OXEDIUM_POOL, None));
+    refresh_pyth_price_updates(&mut account_map, clock.unix_timestamp);
+    for _ in 0..2 {
+        amm.update(&account_map).unwrap();
+    }
+    amm
+}
+
+/// Accounts to update and swap metas as built before the PDAs were cached, deriving the
+/// treasury PDA, the vault PDAs and the treasury ATAs on every call
+fn derive_accounts_and_metas(amm: &OxediumAmm, params: &SwapParams) -> Vec<AccountMeta> {
+    let mut accounts = vec![find_treasury_pda(&amm.program_id)];
+    accounts.extend(&amm.vault_addresses);
+    for vault in amm.vaults.values() {
+        accounts.push(vault.token_mint);
+        accounts.push(vault.pyth_price_account);
+    }
+    black_box(accounts);
+
+    let vault_in = &amm.vaults[&params.source_mint];
+    let vault_out = &amm.vaults[&params.destination_mint];
+    let treasury_pda = find_treasury_pda(&amm.program_id);
+    OxediumSwap {
+        signer: params.token_transfer_authority,
+        mint_in: params.source_mint,
+        mint_out: params.destination_mint,
+        pyth_price_account_in: vault_in.pyth_price_account,
+        pyth_price_account_out: vault_out.pyth_price_account,
+        signer_ata_in: params.source_token_account,
+        signer_ata_out: params.destination_token_account,
+        vault_pda_in: find_vault_pda(&params.source_mint, &amm.program_id),
+        vault_pda_out: find_vault_pda(&params.destination_mint, &amm.program_id),
+        treasury_pda,
+        treasury_ata_in: get_associated_token_address_with_program_id(
+            &treasury_pda,
+            &params.source_mint,
+            &spl_token::ID,
+        ),
+        treasury_ata_out: get_associated_token_address_with_program_id(
+            &treasury_pda,
+            &params.destination_mint,
+            &spl_token::ID,
+        ),
+        associated_token_program: spl_associated_token_account::ID,
+        token_program: spl_token::ID,
+        system_program: system_program::ID,
+    }
+    .to_account_metas(None)
+}
+
+/// Accounts to update and swap metas with the PDAs cached by `update`, against the same
+/// accounts and metas derived on every call
+fn bench_cached_pdas(c: &mut Criterion) {
+    let amm = load_oxedium_amm();
+    let user = Pubkey::new_unique();
+    let swap_params = SwapParams {
+        swap_mode: SwapMode::ExactIn,
+        source_mint: SOL_MINT,
+        destination_mint: USDC_MINT,
+        source_token_account: user,
+        destination_token_account: user,
+        token_transfer_authority: user,
+        quote_mint_to_referrer: None,
+        in_amount: 1_000_000,
+        out_amount: 1_000_000,
+        jupiter_program_id: &JUPITER_PROGRAM_ID,
+        missing_dynamic_accounts_as_default: false,
+    };
+    // Both cases must build the same metas for the comparison to hold
+    assert_eq!(
+        derive_accounts_and_metas(&amm, &swap_params),
+        amm.get_swap_and_account_metas(&swap_params)
+            .unwrap()
+            .account_metas
+    );
+
+    let mut group = c.benchmark_group("oxedium_accounts_and_metas");
+    group.bench_function("cached", |b| {
+        b.iter(|| {
+            black_box(amm.get_accounts_to_update());
+            black_box(
+                amm.get_swap_and_account_metas(black_box(&swap_params))
+                    .unwrap(),
+            );
+        })
+    });
+    group.bench_function("derived", |b| {
+        b.iter(|| black_box(derive_accounts_and_metas(&amm, black_box(&swap_params))))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_cached_pdas);
+criterion_main!(benches);
@@ -2,11 +2,11 @@ use anyhow::Result;
 use jupiter_amm_interface::{Amm, AmmContext, AmmProgramIdToLabel, KeyedAccount};
 use solana_sdk::pubkey::Pubkey;
 use std::collections::HashMap;
-use std::sync::LazyLock;
+use std::sync::{LazyLock, RwLock};
 
 use crate::amms::oxedium_amm::OxediumAmm;
 
-type AmmFromKeyedAccount =
+pub type AmmFromKeyedAccount =
     Box<dyn Fn(&KeyedAccount, &AmmContext) -> Result<Box<dyn Amm + Send + Sync>> + Send + Sync>;
 
 fn wrap_from_keyed_account<T: Amm + Send + Sync + 'static>() -> AmmFromKeyedAccount {
@@ -33,11 +33,59 @@ pub static PROGRAM_ID_TO_AMM_LABEL_WITH_AMM_FROM_KEYED_ACCOUNT: LazyLock<
     m
 });
 
-pub static PROGRAM_ID_TO_LABEL: LazyLock<HashMap<String, String>> = LazyLock::new(|| {
-    let program_id_to_label = HashMap::from_iter(
+/// Program ids registered at runtime, e.g. staging or local validator deployments of a known AMM
+pub static REGISTERED_PROGRAM_ID_TO_AMM_LABEL_WITH_AMM_FROM_KEYED_ACCOUNT: LazyLock<
+    RwLock<HashMap<Pubkey, (&'static str, AmmFromKeyedAccount)>>,
+> = LazyLock::new(Default::default);
+
+/// Registers an extra program id for an AMM, so that `amm_factory` loads its keyed accounts
+pub fn register_program_id<T: Amm + Send + Sync + 'static>(
+    program_id: Pubkey,
+    label: &'static str,
+) {
+    REGISTERED_PROGRAM_ID_TO_AMM_LABEL_WITH_AMM_FROM_KEYED_ACCOUNT
+        .write()
+        .unwrap()
+        .insert(program_id, (label, wrap_from_keyed_account::<T>()));
+}
+
+/// Removes a program id registered with `register_program_id`, returns whether it was registered
+pub fn unregister_program_id(program_id: &Pubkey) -> bool {
+    REGISTERED_PROGRAM_ID_TO_AMM_LABEL_WITH_AMM_FROM_KEYED_ACCOUNT
+        .write()
+        .unwrap()
+        .remove(program_id)
+        .is_some()
+}
+
+/// Labels of the built-in and registered program ids, built-in labels take precedence like
+/// in `amm_factory`
+pub fn program_id_to_label() -> HashMap<String, String> {
+    let mut program_id_to_label = HashMap::from_iter(
+        REGISTERED_PROGRAM_ID_TO_AMM_LABEL_WITH_AMM_FROM_KEYED_ACCOUNT
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(program_id, (label, _))| (program_id.to_string(), (*label).into())),
+    );
+    program_id_to_label.extend(
         PROGRAM_ID_TO_AMM_LABEL_WITH_AMM_FROM_KEYED_ACCOUNT
             .iter()
             .map(|(program_id, (label, _))| (program_id.to_string(), (*label).into())),
     );
     program_id_to_label
-});
\ No newline at end of file
+}
+
+/// Label of a built-in or registered program id
+pub fn program_id_label(program_id: &Pubkey) -> Option<&'static str> {
+    PROGRAM_ID_TO_AMM_LABEL_WITH_AMM_FROM_KEYED_ACCOUNT
+        .get(program_id)
+        .map(|(label, _)| *label)
+        .or_else(|| {
+            REGISTERED_PROGRAM_ID_TO_AMM_LABEL_WITH_AMM_FROM_KEYED_ACCOUNT
+                .read()
+                .unwrap()
+                .get(program_id)
+                .map(|(label, _)| *label)
+        })
+}
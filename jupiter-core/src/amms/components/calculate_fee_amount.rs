@@ -1,4 +1,5 @@
-use anyhow::{anyhow, Result};
+use anyhow::Result;
+use crate::errors::OxediumError;
 
 /// Calculates the resulting amount after applying LP, protocol, and partner fees.
 ///
@@ -26,7 +27,7 @@ pub fn calculate_fee_amount(
     let amount_after_fee = amount
         .checked_sub(lp_fee)
         .and_then(|v| v.checked_sub(protocol_fee))
-        .ok_or_else(|| anyhow!("fee calculation overflow"))?;
+        .ok_or(OxediumError::FeeOverflow)?;
 
     Ok((amount_after_fee, lp_fee, protocol_fee))
 }
@@ -45,7 +46,7 @@ fn fee(amount: u64, bps: u64) -> Result<u64> {
     }
     let f = amount
         .checked_mul(bps)
-        .ok_or_else(|| anyhow!("fee calculation overflow"))?
+        .ok_or(OxediumError::FeeOverflow)?
         / 10_000;
     Ok(f.max(1).min(amount)) // at least 1, but no more than amount
 }
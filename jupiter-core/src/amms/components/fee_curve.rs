@@ -0,0 +1,117 @@
+use serde::Deserialize;
+
+/// Shapes how the swap fee grows with the liquidity deviation of the output vault
+pub trait FeeCurve {
+    /// Maps a deviation in basis points (0..=10_000) to a curved deviation (0..=10_000)
+    ///
+    /// The curve should be non-decreasing, so that a wider imbalance never lowers the fee.
+    /// Quoting does not rely on it, the fee of a swap only depends on the vault state
+    /// before the swap and not on its amount.
+    fn curved_deviation_bps(&self, deviation_bps: u64) -> u64;
+}
+
+/// `x²`, the curve of the current Oxedium program
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct QuadraticFeeCurve;
+
+impl FeeCurve for QuadraticFeeCurve {
+    fn curved_deviation_bps(&self, deviation_bps: u64) -> u64 {
+        deviation_bps * deviation_bps / 10_000
+    }
+}
+
+/// `x`, the fee grows proportionally to the deviation
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LinearFeeCurve;
+
+impl FeeCurve for LinearFeeCurve {
+    fn curved_deviation_bps(&self, deviation_bps: u64) -> u64 {
+        deviation_bps
+    }
+}
+
+/// `x³`, flatter than the quadratic curve for small deviations and steeper for large ones
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CubicFeeCurve;
+
+impl FeeCurve for CubicFeeCurve {
+    fn curved_deviation_bps(&self, deviation_bps: u64) -> u64 {
+        deviation_bps * deviation_bps * deviation_bps / (10_000 * 10_000)
+    }
+}
+
+/// Linear interpolation between `(deviation_bps, curved_deviation_bps)` points,
+/// flat before the first point and after the last one
+#[derive(Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(try_from = "Vec<(u64, u64)>")]
+pub struct PiecewiseFeeCurve {
+    points: Vec<(u64, u64)>,
+}
+
+impl PiecewiseFeeCurve {
+    /// Points must be non-empty, strictly increasing in deviation,
+    /// non-decreasing in curved deviation and within 0..=10_000
+    pub fn new(points: Vec<(u64, u64)>) -> Option<Self> {
+        let in_range = points
+            .iter()
+            .all(|(deviation_bps, curved_bps)| *deviation_bps <= 10_000 && *curved_bps <= 10_000);
+        let monotonic = points
+            .windows(2)
+            .all(|pair| pair[0].0 < pair[1].0 && pair[0].1 <= pair[1].1);
+        (!points.is_empty() && in_range && monotonic).then_some(Self { points })
+    }
+
+    pub fn points(&self) -> &[(u64, u64)] {
+        &self.points
+    }
+}
+
+impl TryFrom<Vec<(u64, u64)>> for PiecewiseFeeCurve {
+    type Error = &'static str;
+
+    fn try_from(points: Vec<(u64, u64)>) -> Result<Self, Self::Error> {
+        Self::new(points)
+            .ok_or("piecewise fee curve points must be non-empty, increasing and within 0..=10000")
+    }
+}
+
+impl FeeCurve for PiecewiseFeeCurve {
+    fn curved_deviation_bps(&self, deviation_bps: u64) -> u64 {
+        let upper_index = self
+            .points
+            .partition_point(|(point_deviation_bps, _)| *point_deviation_bps <= deviation_bps);
+        match (upper_index.checked_sub(1), self.points.get(upper_index)) {
+            (Some(lower_index), Some(&(x1, y1))) => {
+                let (x0, y0) = self.points[lower_index];
+                y0 + (y1 - y0) * (deviation_bps - x0) / (x1 - x0)
+            }
+            (Some(lower_index), None) => self.points[lower_index].1,
+            (None, _) => self.points[0].1,
+        }
+    }
+}
+
+/// Fee curve selected in the keyed account params, e.g. `{"feeCurve": {"type": "cubic"}}`
+/// or `{"feeCurve": {"type": "piecewise", "points": [[0, 0], [5000, 1000], [10000, 10000]]}}`
+#[derive(Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum FeeCurveModel {
+    #[default]
+    Quadratic,
+    Linear,
+    Cubic,
+    Piecewise {
+        points: PiecewiseFeeCurve,
+    },
+}
+
+impl FeeCurve for FeeCurveModel {
+    fn curved_deviation_bps(&self, deviation_bps: u64) -> u64 {
+        match self {
+            FeeCurveModel::Quadratic => QuadraticFeeCurve.curved_deviation_bps(deviation_bps),
+            FeeCurveModel::Linear => LinearFeeCurve.curved_deviation_bps(deviation_bps),
+            FeeCurveModel::Cubic => CubicFeeCurve.curved_deviation_bps(deviation_bps),
+            FeeCurveModel::Piecewise { points } => points.curved_deviation_bps(deviation_bps),
+        }
+    }
+}
@@ -1,4 +1,7 @@
-use crate::states::Vault;
+use crate::{
+    components::{FeeCurve, QuadraticFeeCurve},
+    states::Vault,
+};
 
 /// Calculates the swap fee (in basis points) based on the liquidity imbalance
 /// between the input and output vaults.
@@ -18,6 +21,24 @@ use crate::states::Vault;
 pub fn fees_setting(
     vault_in: &Vault,
     vault_out: &Vault,
+) -> u64 {
+    fees_setting_with_curve(vault_in, vault_out, &QuadraticFeeCurve)
+}
+
+/// Same as `fees_setting`, with the deviation shaped by `fee_curve`
+/// instead of the quadratic curve
+///
+/// # Arguments
+/// * `vault_in`  - The source vault for the swap
+/// * `vault_out` - The destination vault for the swap
+/// * `fee_curve` - The curve applied to the output vault deviation
+///
+/// # Returns
+/// * `u64` - The calculated total fee in basis points (bps)
+pub fn fees_setting_with_curve(
+    vault_in: &Vault,
+    vault_out: &Vault,
+    fee_curve: &dyn FeeCurve,
 ) -> u64 {
     // Change in liquidity relative to the initial state
     let delta_in: i64 =
@@ -33,24 +54,15 @@ pub fn fees_setting(
 
     // Absolute deviation of output vault liquidity from its initial value,
     // expressed in basis points (0..10_000)
-    let deviation_bps: u64 = if vault_out.current_liquidity > vault_out.initial_liquidity {
-        ((vault_out.current_liquidity - vault_out.initial_liquidity) * 10_000)
-            / vault_out.initial_liquidity
-    } else {
-        ((vault_out.initial_liquidity - vault_out.current_liquidity) * 10_000)
-            / vault_out.initial_liquidity
-    };
-
-    // Cap deviation at 100% to avoid excessive or undefined fee growth
-    let deviation_bps = deviation_bps.min(10_000);
+    let deviation_bps = vault_deviation_bps(vault_out);
 
-    // Apply a quadratic (x²) curve to the deviation:
+    // Apply the fee curve to the deviation, e.g. with the quadratic (x²) curve:
     // - small deviations increase the fee slowly
     // - large deviations increase the fee aggressively
     //
     // Result is still scaled to 0..10_000
     let curved_deviation_bps =
-        deviation_bps * deviation_bps / 10_000;
+        fee_curve.curved_deviation_bps(deviation_bps).min(10_000);
 
     // Maximum possible fee is capped at 10_000 bps (100%)
     const MAX_FEE_BPS: u64 = 10_000;
@@ -65,3 +77,26 @@ pub fn fees_setting(
 
     total_fee_bps
 }
+
+/// Absolute deviation of the vault liquidity from its initial liquidity, in basis points
+///
+/// # Arguments
+/// * `vault` - The vault to measure
+///
+/// # Returns
+/// * `u64` - The deviation in basis points, capped at 10_000 (100%).
+///   A vault without initial liquidity counts as fully deviated
+pub fn vault_deviation_bps(vault: &Vault) -> u64 {
+    let deviation_bps: u64 = if vault.current_liquidity > vault.initial_liquidity {
+        ((vault.current_liquidity - vault.initial_liquidity) * 10_000)
+            .checked_div(vault.initial_liquidity)
+            .unwrap_or(10_000)
+    } else {
+        ((vault.initial_liquidity - vault.current_liquidity) * 10_000)
+            .checked_div(vault.initial_liquidity)
+            .unwrap_or(10_000)
+    };
+
+    // Cap deviation at 100% to avoid excessive or undefined fee growth
+    deviation_bps.min(10_000)
+}
\ No newline at end of file
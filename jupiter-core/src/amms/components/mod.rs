@@ -2,8 +2,14 @@ pub use swap_math::*;
 pub use calculate_fee_amount::*;
 pub use fees_setting::*;
 pub use raw_amount_out::*;
+pub use oracle_price::*;
+pub use fee_curve::*;
+pub use staker_yield::*;
 
 pub mod swap_math;
 pub mod calculate_fee_amount;
 pub mod fees_setting;
-pub mod raw_amount_out;
\ No newline at end of file
+pub mod raw_amount_out;
+pub mod oracle_price;
+pub mod fee_curve;
+pub mod staker_yield;
\ No newline at end of file
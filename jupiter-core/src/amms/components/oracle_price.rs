@@ -0,0 +1,41 @@
+use pyth_solana_receiver_sdk::price_update::PriceFeedMessage;
+
+/// An oracle price, worth `price * 10^exponent` USD per whole token
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OraclePrice {
+    pub price: u64,
+    pub exponent: i32,
+}
+
+impl OraclePrice {
+    pub fn new(price: u64, exponent: i32) -> Self {
+        Self { price, exponent }
+    }
+
+    /// Price of a Pyth price message, `None` when the price is zero or negative
+    pub fn from_price_message(price_message: &PriceFeedMessage) -> Option<Self> {
+        u64::try_from(price_message.price)
+            .ok()
+            .filter(|price| *price > 0)
+            .map(|price| Self::new(price, price_message.exponent))
+    }
+
+    /// Lower bound of the confidence interval `price - conf`
+    pub fn lower_bound(self, conf: u64) -> Self {
+        Self::new(self.price.saturating_sub(conf), self.exponent)
+    }
+
+    /// Upper bound of the confidence interval `price + conf`
+    pub fn upper_bound(self, conf: u64) -> Self {
+        Self::new(self.price.saturating_add(conf), self.exponent)
+    }
+
+    /// Width of the confidence interval relative to the price, in basis points
+    pub fn confidence_bps(self, conf: u64) -> u64 {
+        if self.price == 0 {
+            return u64::MAX;
+        }
+        let bps = conf as u128 * 10_000 / self.price as u128;
+        u64::try_from(bps).unwrap_or(u64::MAX)
+    }
+}
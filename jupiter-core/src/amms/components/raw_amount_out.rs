@@ -1,15 +1,22 @@
-use anyhow::{anyhow, Result};
-use crate::utils::SCALE;
+use anyhow::Result;
+use ethnum::U256;
+use crate::{components::OraclePrice, errors::OxediumError, utils::SCALE};
 
 /// Calculates the raw output amount for a token swap using fixed-point math.
-/// Supports dust swaps by avoiding early division and rounding only once at the end.
+///
+/// Prices carry their own exponent, so feeds with different exponents are
+/// normalised exactly instead of assuming a fixed 1e8 scale.
+///
+/// Every step rounds down where the on-chain program does, so quotes match it
+/// bit-for-bit. The steps run on 256-bit integers: an input whose result fits
+/// in `u64` never overflows an intermediate.
 ///
 /// # Arguments
 /// * `amount_in` - Input token amount in smallest units
 /// * `decimals_in` - Decimals of the input token
 /// * `decimals_out` - Decimals of the output token
-/// * `price_in` - Price of the input token (e.g. Pyth price, scaled)
-/// * `price_out` - Price of the output token (e.g. Pyth price, scaled)
+/// * `price_in` - Price of the input token (e.g. Pyth price and exponent)
+/// * `price_out` - Price of the output token (e.g. Pyth price and exponent)
 ///
 /// # Returns
 /// * `Result<u64>` - Output token amount in smallest units
@@ -17,40 +24,62 @@ pub fn raw_amount_out(
     amount_in: u64,
     decimals_in: u32,
     decimals_out: u32,
-    price_in: u64,
-    price_out: u64,
+    price_in: OraclePrice,
+    price_out: OraclePrice,
 ) -> Result<u64> {
-    let amount_in = amount_in as u128;
-    let price_in = price_in as u128;
-    let price_out = price_out as u128;
+    // A zero price cannot convert between tokens
+    if price_in.price == 0 || price_out.price == 0 {
+        return Err(OxediumError::NonPositiveOraclePrice { price: 0 }.into());
+    }
+
+    let price_in_value = U256::from(price_in.price);
+    let price_out_value = U256::from(price_out.price);
 
     // 1. Convert input amount into fixed-point token representation
-    let amount_fp = amount_in
-        .checked_mul(SCALE)
-        .ok_or_else(|| anyhow!("Overflow in mul during amount_fp calculation"))?
-        .checked_div(10u128.pow(decimals_in as u32))
-        .ok_or_else(|| anyhow!("Overflow in div during amount_fp calculation"))?;
+    let amount_fp = div_pow10(U256::from(amount_in) * U256::from(SCALE), decimals_in);
 
     // 2. Convert input token amount into USD value (still fixed-point)
-    let usd_fp = amount_fp
-        .checked_mul(price_in)
-        .ok_or_else(|| anyhow!("Overflow in mul during usd_fp calculation"))?
-        .checked_div(1_000_000_00) // adjust scale if needed
-        .ok_or_else(|| anyhow!("Overflow in div during usd_fp calculation"))?;
+    let usd_fp = amount_fp * price_in_value;
+    let usd_fp = if price_in.exponent < 0 {
+        div_pow10(usd_fp, price_in.exponent.unsigned_abs())
+    } else {
+        mul_pow10(usd_fp, price_in.exponent.unsigned_abs(), "usd_fp")?
+    };
 
     // 3. Convert USD value into output token amount (fixed-point)
-    let out_fp = usd_fp
-        .checked_mul(1_000_000_00)
-        .ok_or_else(|| anyhow!("Overflow in mul during out_fp calculation"))?
-        .checked_div(price_out)
-        .ok_or_else(|| anyhow!("Overflow in div during out_fp calculation"))?;
+    let out_fp = if price_out.exponent < 0 {
+        mul_pow10(usd_fp, price_out.exponent.unsigned_abs(), "out_fp")? / price_out_value
+    } else {
+        // A divisor above 256 bits exceeds the USD value, the output is dust
+        match pow10(price_out.exponent.unsigned_abs())
+            .and_then(|scale| scale.checked_mul(price_out_value))
+        {
+            Some(price_out_scaled) => usd_fp / price_out_scaled,
+            None => U256::ZERO,
+        }
+    };
 
     // 4. Convert fixed-point output into smallest output token units
-    let out = out_fp
-        .checked_mul(10u128.pow(decimals_out as u32))
-        .ok_or_else(|| anyhow!("Overflow in mul during final conversion"))?
-        .checked_div(SCALE)
-        .ok_or_else(|| anyhow!("Overflow in div during final conversion"))?;
+    let out = mul_pow10(out_fp, decimals_out, "amount_out")? / U256::from(SCALE);
+    if out > U256::from(u64::MAX) {
+        return Err(OxediumError::MathOverflow("amount_out").into());
+    }
+
+    Ok(out.as_u64())
+}
+
+fn pow10(exponent: u32) -> Option<U256> {
+    U256::from(10u8).checked_pow(exponent)
+}
+
+/// `value * 10^exponent`, failing with `MathOverflow(step)` above 256 bits
+fn mul_pow10(value: U256, exponent: u32, step: &'static str) -> Result<U256> {
+    Ok(pow10(exponent)
+        .and_then(|scale| value.checked_mul(scale))
+        .ok_or(OxediumError::MathOverflow(step))?)
+}
 
-    u64::try_from(out).map_err(|_| anyhow!("Overflow converting final output to u64"))
+/// `value / 10^exponent` rounded down, zero once the power of ten exceeds 256 bits
+fn div_pow10(value: U256, exponent: u32) -> U256 {
+    pow10(exponent).map_or(U256::ZERO, |scale| value / scale)
 }
@@ -0,0 +1,103 @@
+use anyhow::Result;
+use crate::{
+    errors::OxediumError,
+    states::{Staker, Vault},
+    utils::{SCALE, UNSTAKE_LOW_LIQUIDITY_BPS, UNSTAKE_LOW_LIQUIDITY_PCT},
+};
+
+/// Value of an LP position in a vault, in vault token units
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StakerPosition {
+    /// LP tokens held by the staker owner
+    pub lp_balance: u64,
+    /// Vault tokens paid out for unstaking `lp_balance`
+    pub lp_value: u64,
+    /// Yield settled on the staker plus the yield accrued since its last checkpoint
+    pub claimable_yield: u64,
+}
+
+/// Calculates the yield a staker can claim from a vault
+///
+/// # Arguments
+/// * `vault` - The vault the staker provides liquidity to
+/// * `staker` - The staker position checkpoint
+/// * `lp_balance` - LP tokens held by the staker owner
+///
+/// # Returns
+/// * `Result<u64>` - `pending_claim` plus `lp_balance * (cumulative_yield_per_lp - last_cumulative_yield) / SCALE`,
+///   rounded down like the program
+pub fn claimable_yield(
+    vault: &Vault,
+    staker: &Staker,
+    lp_balance: u64,
+) -> Result<u64> {
+    // Yield accrued per LP token since the staker last checkpoint
+    let yield_per_lp = vault
+        .cumulative_yield_per_lp
+        .checked_sub(staker.last_cumulative_yield)
+        .ok_or(OxediumError::MathOverflow("yield per lp since last checkpoint"))?;
+
+    let accrued_yield = (lp_balance as u128)
+        .checked_mul(yield_per_lp)
+        .ok_or(OxediumError::MathOverflow("accrued yield"))?
+        / SCALE;
+
+    let claimable_yield = u64::try_from(accrued_yield)
+        .ok()
+        .and_then(|accrued_yield| accrued_yield.checked_add(staker.pending_claim))
+        .ok_or(OxediumError::MathOverflow("claimable yield"))?;
+
+    Ok(claimable_yield)
+}
+
+/// Calculates the vault tokens the program pays out for unstaking an amount of LP tokens
+///
+/// LP tokens are minted 1:1 for deposits and redeemed 1:1, unless the vault holds less
+/// than `UNSTAKE_LOW_LIQUIDITY_PCT` of its initial liquidity. Below that, Unstaking only
+/// pays out `UNSTAKE_LOW_LIQUIDITY_BPS` of the LP amount, at least 1 token.
+///
+/// # Arguments
+/// * `vault` - The vault backing the LP tokens
+/// * `lp_amount` - LP tokens to value
+///
+/// # Returns
+/// * `Result<u64>` - Vault tokens paid out for `lp_amount`, rounded down like the program
+pub fn lp_token_value(
+    vault: &Vault,
+    lp_amount: u64,
+) -> Result<u64> {
+    // Share of the initial liquidity still in the vault, in percent
+    let liquidity_pct = (vault.current_liquidity as u128 * 100)
+        .checked_div(vault.initial_liquidity as u128)
+        .ok_or(OxediumError::MathOverflow("vault liquidity ratio"))?;
+    if liquidity_pct >= UNSTAKE_LOW_LIQUIDITY_PCT || lp_amount == 0 {
+        return Ok(lp_amount);
+    }
+
+    let lp_value = (lp_amount as u128 * UNSTAKE_LOW_LIQUIDITY_BPS / 10_000)
+        .max(1)
+        .min(lp_amount as u128);
+
+    Ok(lp_value as u64)
+}
+
+/// Values a staker position: its LP tokens and the yield it can claim
+///
+/// # Arguments
+/// * `vault` - The vault the staker provides liquidity to
+/// * `staker` - The staker position checkpoint
+/// * `lp_balance` - LP tokens held by the staker owner
+///
+/// # Returns
+/// * `Result<StakerPosition>` - The LP value and claimable yield of the position
+pub fn staker_position(
+    vault: &Vault,
+    staker: &Staker,
+    lp_balance: u64,
+) -> Result<StakerPosition> {
+    Ok(StakerPosition {
+        lp_balance,
+        lp_value: lp_token_value(vault, lp_balance)?,
+        claimable_yield: claimable_yield(vault, staker, lp_balance)?,
+    })
+}
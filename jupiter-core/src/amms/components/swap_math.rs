@@ -1,6 +1,7 @@
-use anyhow::{anyhow, Result};
+use anyhow::Result;
 use crate::{
-    components::{calculate_fee_amount, fees_setting, raw_amount_out},
+    errors::OxediumError,
+    components::{calculate_fee_amount, fees_setting_with_curve, raw_amount_out, FeeCurve, OraclePrice},
     states::Vault,
 };
 
@@ -16,28 +17,31 @@ pub struct SwapMathResult {
 ///
 /// # Arguments
 /// * `amount_in` - Amount of input tokens
-/// * `price_in` - Price of input token (scaled)
-/// * `price_out` - Price of output token (scaled)
+/// * `price_in` - Price of input token with its exponent
+/// * `price_out` - Price of output token with its exponent
 /// * `decimals_in` - Number of decimals of the input token
 /// * `decimals_out` - Number of decimals of the output token
 /// * `vault_in` - Input token vault info
 /// * `vault_out` - Output token vault info
-/// * `treasury` - Treasury info containing protocol fees
+/// * `protocol_fee_bps` - Protocol fee in basis points
+/// * `fee_curve` - Curve applied to the output vault deviation for the swap fee
 ///
 /// # Returns
 /// `SwapMathResult` containing raw output, net output, and all individual fees
+#[allow(clippy::too_many_arguments)]
 pub fn compute_swap_math(
     amount_in: u64,
-    price_in: u64,
-    price_out: u64,
+    price_in: OraclePrice,
+    price_out: OraclePrice,
     decimals_in: u32,
     decimals_out: u32,
     vault_in: &Vault,
     vault_out: &Vault,
     protocol_fee_bps: u64,
+    fee_curve: &dyn FeeCurve,
 ) -> Result<SwapMathResult> {
     // Get the LP fee and protocol fee
-    let swap_fee_bps = fees_setting(&vault_in, &vault_out);
+    let swap_fee_bps = fees_setting_with_curve(&vault_in, &vault_out, fee_curve);
 
     // 1️⃣ Calculate the raw output amount before any fees
     let raw_out = raw_amount_out(
@@ -46,11 +50,14 @@ pub fn compute_swap_math(
         decimals_out,
         price_in,
         price_out,
-    ).map_err(|e| anyhow!("raw_amount_out failed: {:?}", e))?;
+    )?;
 
     // 2️⃣ Ensure the total fees do not exceed 100%
-    if swap_fee_bps + protocol_fee_bps > 10_000 {
-        return Err(anyhow!("Total fee exceeds 100%"));
+    let total_fee_bps = swap_fee_bps
+        .checked_add(protocol_fee_bps)
+        .ok_or(OxediumError::FeeOverflow)?;
+    if total_fee_bps > 10_000 {
+        return Err(OxediumError::TotalFeeTooHigh { total_fee_bps }.into());
     }
 
     // 3️⃣ Calculate individual fees and net output after fees
@@ -58,19 +65,42 @@ pub fn compute_swap_math(
         raw_out,
         swap_fee_bps,
         protocol_fee_bps,
-    ).map_err(|e| anyhow!("calculate_fee_amount failed: {:?}", e))?;
+    )?;
 
     // 4️⃣ Check if the vault has sufficient liquidity
     let total_out = after_fee
         .checked_add(lp_fee)
         .and_then(|v| v.checked_add(protocol_fee))
-        .ok_or_else(|| anyhow!("Overflow when summing fees"))?;
+        .ok_or(OxediumError::FeeOverflow)?;
 
     if vault_out.current_liquidity < total_out {
-        return Err(anyhow!("Insufficient liquidity in vault"));
+        return Err(OxediumError::InsufficientLiquidity {
+            mint: vault_out.token_mint,
+            available: vault_out.current_liquidity,
+            required: total_out,
+        }
+        .into());
     }
 
-    // 5️⃣ Return the computed result
+    // 5️⃣ Check the input vault stays within its capacity
+    let liquidity_in_after = vault_in.current_liquidity.checked_add(amount_in);
+    if liquidity_in_after.is_none_or(|liquidity| liquidity > vault_in.max_liquidity) {
+        return Err(OxediumError::VaultCapacityExceeded {
+            mint: vault_in.token_mint,
+            current_liquidity: vault_in.current_liquidity,
+            amount_in,
+            max_liquidity: vault_in.max_liquidity,
+        }
+        .into());
+    }
+
+    // 6️⃣ The program credits the LP fee per LP token of the output vault,
+    // it fails on a vault without initial liquidity
+    if vault_out.initial_liquidity == 0 {
+        return Err(OxediumError::NoInitialLiquidity { mint: vault_out.token_mint }.into());
+    }
+
+    // 7️⃣ Return the computed result
     Ok(SwapMathResult {
         swap_fee_bps,
         raw_amount_out: raw_out,
@@ -79,3 +109,156 @@ pub fn compute_swap_math(
         protocol_fee_amount: protocol_fee,
     })
 }
+
+/// Computes the minimal input amount required to receive at least `net_amount_out`
+/// output tokens, by inverting `compute_swap_math`
+///
+/// The raw output is non-decreasing in the input amount, but the LP and protocol fees are
+/// floored separately on it, so the net output drops by one unit whenever both fees step
+/// up at once (e.g. 30 and 25 bps net 1990 out of 1999 but 1989 out of 2000). An input
+/// netting enough output is found with an exponential search for an upper bound followed
+/// by a binary search, then every distinct smaller raw output that could still net the
+/// requested amount is tried to find the minimal input. Each round jumps to the raw
+/// output of the input just below the last one, so a pair whose raw output steps by
+/// many units per input unit only visits the raw outputs it can reach.
+///
+/// # Arguments
+/// * `net_amount_out` - Exact amount of output tokens the user wants to receive
+/// * `price_in` - Price of input token with its exponent
+/// * `price_out` - Price of output token with its exponent
+/// * `decimals_in` - Number of decimals of the input token
+/// * `decimals_out` - Number of decimals of the output token
+/// * `vault_in` - Input token vault info
+/// * `vault_out` - Output token vault info
+/// * `protocol_fee_bps` - Protocol fee in basis points
+/// * `fee_curve` - Curve applied to the output vault deviation for the swap fee
+///
+/// # Returns
+/// `(amount_in, SwapMathResult)` with the minimal input amount and the swap math for it
+#[allow(clippy::too_many_arguments)]
+pub fn compute_swap_math_exact_out(
+    net_amount_out: u64,
+    price_in: OraclePrice,
+    price_out: OraclePrice,
+    decimals_in: u32,
+    decimals_out: u32,
+    vault_in: &Vault,
+    vault_out: &Vault,
+    protocol_fee_bps: u64,
+    fee_curve: &dyn FeeCurve,
+) -> Result<(u64, SwapMathResult)> {
+    if net_amount_out == 0 {
+        return Err(OxediumError::ZeroExactOutAmount.into());
+    }
+
+    let swap = |amount_in: u64| {
+        compute_swap_math(
+            amount_in,
+            price_in,
+            price_out,
+            decimals_in,
+            decimals_out,
+            vault_in,
+            vault_out,
+            protocol_fee_bps,
+            fee_curve,
+        )
+    };
+    let raw_out = |amount_in: u64| {
+        raw_amount_out(amount_in, decimals_in, decimals_out, price_in, price_out)
+    };
+    let swap_fee_bps = fees_setting_with_curve(vault_in, vault_out, fee_curve);
+    let is_too_small = |amount_in: u64| match swap(amount_in) {
+        Ok(result) => result.net_amount_out < net_amount_out,
+        // Dust inputs can fail the fee calculation, they are too small whenever
+        // the raw output is below the requested amount or the fees exceed it
+        Err(_) => raw_out(amount_in).is_ok_and(|raw_out| {
+            raw_out < net_amount_out
+                || calculate_fee_amount(raw_out, swap_fee_bps, protocol_fee_bps).is_err()
+        }),
+    };
+
+    // 1️⃣ Exponential search: `low` always yields too little output,
+    // `high` yields enough output or fails to quote
+    let mut low: u64 = 0;
+    let mut high: u64 = 1;
+    while is_too_small(high) {
+        low = high;
+        high = high
+            .checked_mul(2)
+            .ok_or(OxediumError::ExactOutNotFound { net_amount_out })?;
+    }
+
+    // 2️⃣ Binary search for the smallest input that is not too small
+    while high - low > 1 {
+        let mid = low + (high - low) / 2;
+        if is_too_small(mid) {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+
+    // 3️⃣ The candidate either satisfies the exact out amount or surfaces
+    // the reason why no input can (e.g. insufficient liquidity)
+    let mut amount_in = high;
+    let mut result = swap(amount_in)?;
+    if result.net_amount_out < net_amount_out {
+        return Err(OxediumError::ExactOutNotFound { net_amount_out }.into());
+    }
+
+    // 4️⃣ The net output is not monotonic, a smaller raw output may still net the
+    // requested amount. Walk the distinct raw outputs below the candidate down to the
+    // lowest one that can and keep the smallest input reaching any of them that nets enough
+    let min_raw_out = min_raw_amount_out(
+        net_amount_out,
+        swap_fee_bps + protocol_fee_bps,
+    );
+    let mut next_amount_in = amount_in;
+    loop {
+        // The next smaller raw output is the one of the input just below,
+        // the raw output of a zero input is zero
+        let target_raw_out = raw_out(next_amount_in - 1)?;
+        if target_raw_out < min_raw_out {
+            break;
+        }
+
+        // Smallest input with a raw output of at least `target_raw_out`,
+        // the raw output is non-decreasing in the input
+        let mut low: u64 = 0;
+        let mut high = next_amount_in - 1;
+        while high - low > 1 {
+            let mid = low + (high - low) / 2;
+            if raw_out(mid)? < target_raw_out {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        next_amount_in = high;
+
+        if let Ok(candidate) = swap(high) {
+            if candidate.net_amount_out >= net_amount_out {
+                amount_in = high;
+                result = candidate;
+            }
+        }
+    }
+
+    Ok((amount_in, result))
+}
+
+/// Lowest raw output that can net `net_amount_out` once both fees are taken
+///
+/// Each fee is floored on the raw output, so it takes at most one unit less than its
+/// exact share and `net < raw * (10_000 - total_fee_bps) / 10_000 + 2`
+fn min_raw_amount_out(net_amount_out: u64, total_fee_bps: u64) -> u64 {
+    if total_fee_bps >= 10_000 {
+        return net_amount_out;
+    }
+    let min_raw_out = net_amount_out.saturating_sub(2) as u128 * 10_000
+        / (10_000 - total_fee_bps) as u128;
+    u64::try_from(min_raw_out)
+        .unwrap_or(u64::MAX)
+        .max(net_amount_out)
+}
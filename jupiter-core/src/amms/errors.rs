@@ -0,0 +1,91 @@
+use solana_sdk::pubkey::Pubkey;
+use thiserror::Error;
+
+/// Errors returned by `OxediumAmm`, wrapped in `anyhow::Error` and recoverable with `downcast_ref`
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum OxediumError {
+    #[error("Invalid Oxedium params: {0}")]
+    InvalidParams(String),
+    #[error("Invalid vault address {0}")]
+    InvalidVaultAddress(String),
+    #[error("Invalid account {address}: {reason}")]
+    InvalidAccount { address: Pubkey, reason: String },
+    #[error("Account {address} is owned by {owner}, not the Oxedium program {program_id}")]
+    InvalidAccountOwner {
+        address: Pubkey,
+        owner: Pubkey,
+        program_id: Pubkey,
+    },
+    #[error("Account {address} discriminator {actual:02x?} does not match {expected:02x?}")]
+    InvalidDiscriminator {
+        address: Pubkey,
+        expected: [u8; 8],
+        actual: [u8; 8],
+    },
+    #[error("Vault for mint {mint} not found")]
+    VaultNotFound { mint: Pubkey },
+    #[error("Vault for staker mint {mint} is not tracked")]
+    StakerVaultNotFound { mint: Pubkey },
+    #[error("Staker account {address} is not the staker PDA {expected} of its owner and vault")]
+    StakerAddressMismatch { address: Pubkey, expected: Pubkey },
+    #[error("Mint info for {mint} not found")]
+    MintNotFound { mint: Pubkey },
+    #[error("Oracle {oracle} not found")]
+    OracleNotFound { oracle: Pubkey },
+    #[error("Oracle {oracle} is owned by {owner}, not the Pyth receiver program")]
+    InvalidOracleOwner { oracle: Pubkey, owner: Pubkey },
+    #[error("Oracle {oracle} price update is not fully verified")]
+    OracleNotFullyVerified { oracle: Pubkey },
+    #[error("Oracle {oracle} feed id {actual:02x?} does not match the feed id {expected:02x?} of mint {mint}")]
+    OracleFeedIdMismatch {
+        oracle: Pubkey,
+        mint: Pubkey,
+        expected: [u8; 32],
+        actual: [u8; 32],
+    },
+    #[error("No Pyth feed id for vault mint {mint}, add it to the feedIds params")]
+    MissingFeedId { mint: Pubkey },
+    #[error("Oracle price {price} is not positive")]
+    NonPositiveOraclePrice { price: i64 },
+    #[error("Stale oracle {oracle}: price is {age}s old, max age is {max_age}s")]
+    StaleOracle {
+        oracle: Pubkey,
+        age: i64,
+        max_age: u64,
+    },
+    #[error("Oracle {oracle} confidence is {confidence_bps} bps of the price, max is {max_confidence_bps} bps")]
+    OracleConfidenceTooWide {
+        oracle: Pubkey,
+        confidence_bps: u64,
+        max_confidence_bps: u64,
+    },
+    #[error("Vault for mint {mint} is inactive")]
+    VaultInactive { mint: Pubkey },
+    #[error("Vault for mint {mint} has no initial liquidity to credit the LP fee to")]
+    NoInitialLiquidity { mint: Pubkey },
+    #[error("Vault for mint {mint} capacity exceeded: {current_liquidity} + {amount_in} > {max_liquidity}")]
+    VaultCapacityExceeded {
+        mint: Pubkey,
+        current_liquidity: u64,
+        amount_in: u64,
+        max_liquidity: u64,
+    },
+    #[error("Insufficient liquidity in vault for mint {mint}: {available} available, {required} required")]
+    InsufficientLiquidity {
+        mint: Pubkey,
+        available: u64,
+        required: u64,
+    },
+    #[error("Total fee of {total_fee_bps} bps exceeds 100%")]
+    TotalFeeTooHigh { total_fee_bps: u64 },
+    #[error("Fee calculation overflow")]
+    FeeOverflow,
+    #[error("Math overflow computing {0}")]
+    MathOverflow(&'static str),
+    #[error("Exact out amount must be greater than zero")]
+    ZeroExactOutAmount,
+    #[error("Unable to find input amount for exact out amount {net_amount_out}")]
+    ExactOutNotFound { net_amount_out: u64 },
+    #[error("Mint {mint} of token program {token_program} is not an SPL Token mint")]
+    UnsupportedTokenProgram { mint: Pubkey, token_program: Pubkey },
+}
@@ -1,7 +1,10 @@
 use anyhow::Result;
 use jupiter_amm_interface::{Amm, AmmContext, KeyedAccount};
 
-use crate::amms::amm_program_id_to_labels::PROGRAM_ID_TO_AMM_LABEL_WITH_AMM_FROM_KEYED_ACCOUNT;
+use crate::amms::amm_program_id_to_labels::{
+    PROGRAM_ID_TO_AMM_LABEL_WITH_AMM_FROM_KEYED_ACCOUNT,
+    REGISTERED_PROGRAM_ID_TO_AMM_LABEL_WITH_AMM_FROM_KEYED_ACCOUNT,
+};
 
 pub fn amm_factory(
     keyed_account: &KeyedAccount,
@@ -9,7 +12,13 @@ pub fn amm_factory(
 ) -> Result<Option<Box<dyn Amm + Send + Sync>>> {
     let owner = keyed_account.account.owner;
 
-    PROGRAM_ID_TO_AMM_LABEL_WITH_AMM_FROM_KEYED_ACCOUNT
+    if let Some((_, f)) = PROGRAM_ID_TO_AMM_LABEL_WITH_AMM_FROM_KEYED_ACCOUNT.get(&owner) {
+        return f(keyed_account, amm_context).map(Some);
+    }
+
+    REGISTERED_PROGRAM_ID_TO_AMM_LABEL_WITH_AMM_FROM_KEYED_ACCOUNT
+        .read()
+        .unwrap()
         .get(&owner)
         .map(|(_, f)| f(keyed_account, amm_context))
         .transpose()
@@ -3,6 +3,7 @@ pub mod amm;
 pub mod states;
 pub mod components;
 pub mod utils;
+pub mod errors;
 pub mod test_harness;
 pub mod loader;
 pub mod amm_program_id_to_labels;
@@ -1,49 +1,652 @@
 use std::collections::HashSet;
+use std::str::FromStr;
+use std::sync::atomic::Ordering;
+use std::sync::LazyLock;
 
 use ahash::HashMap;
 use anchor_lang::system_program;
-use anchor_lang::{prelude::AccountMeta, AnchorDeserialize};
+use anchor_lang::{AnchorDeserialize, ToAccountMetas};
 use anyhow::Result;
 use borsh::BorshDeserialize;
 use jupiter_amm_interface::{
-    AccountMap, Amm, AmmContext, AmmLabel, AmmProgramIdToLabel, KeyedAccount, Quote, QuoteParams,
-    Swap, SwapAndAccountMetas, SwapParams,
+    AccountMap, Amm, AmmContext, AmmLabel, AmmProgramIdToLabel, ClockRef, KeyedAccount, Quote,
+    QuoteParams, Swap, SwapAndAccountMetas, SwapMode, SwapParams,
+};
+use program_interfaces::jupiter_dex_interfaces::client::accounts::OxediumSwap;
+use pyth_solana_receiver_sdk::price_update::{
+    get_feed_id_from_hex, FeedId, PriceUpdateV2, VerificationLevel,
 };
-use pyth_solana_receiver_sdk::price_update::PriceUpdateV2;
 use rust_decimal::Decimal;
-use solana_sdk::program_pack::Pack;
+use serde::Deserialize;
+use solana_sdk::account::Account;
 use solana_sdk::pubkey::*;
-use spl_associated_token_account::get_associated_token_address;
-use spl_token::state::Mint;
+use spl_associated_token_account::get_associated_token_address_with_program_id;
 
+use crate::amms::amm_program_id_to_labels::{program_id_label, register_program_id};
 use crate::states::Treasury;
 use crate::{
-    components::compute_swap_math,
-    states::Vault,
-    utils::{OXEDIUM_SEED, TREASURY_SEED, VAULT_SEED},
+    components::{
+        compute_swap_math, compute_swap_math_exact_out, staker_position, vault_deviation_bps,
+        FeeCurveModel, OraclePrice, StakerPosition,
+    },
+    errors::OxediumError,
+    states::{Staker, Vault},
+    utils::{
+        parse_mint_info, MintInfo, OXEDIUM_SEED, PRICE_UPDATE_V2_DISCRIMINATOR, SCALE,
+        STAKER_DISCRIMINATOR, STAKER_SEED, TREASURY_DISCRIMINATOR, TREASURY_SEED,
+        VAULT_DISCRIMINATOR, VAULT_SEED,
+    },
 };
 
-pub const OXEDIUM_PROGRAM_ID: Pubkey = Pubkey::from_str_const("oxe1SKL52HMLBDT2JQvdxscA1LbVc4EEwwSdNZcnDVH");
+pub const OXEDIUM_PROGRAM_ID: Pubkey =
+    Pubkey::from_str_const("oxe1SKL52HMLBDT2JQvdxscA1LbVc4EEwwSdNZcnDVH");
 
 pub const SOL_MINT: Pubkey = Pubkey::from_str_const("So11111111111111111111111111111111111111112");
 pub const USDC_MINT: Pubkey =
     Pubkey::from_str_const("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v");
 
-const ALL_POSSIBLE_MINTS: &[Pubkey] = &[SOL_MINT, USDC_MINT];
+/// Number of accounts taken by `oxedium_swap`, as generated from the IDL
+static OXEDIUM_SWAP_ACCOUNTS_LEN: LazyLock<usize> = LazyLock::new(|| {
+    OxediumSwap {
+        signer: Pubkey::default(),
+        mint_in: Pubkey::default(),
+        mint_out: Pubkey::default(),
+        pyth_price_account_in: Pubkey::default(),
+        pyth_price_account_out: Pubkey::default(),
+        signer_ata_in: Pubkey::default(),
+        signer_ata_out: Pubkey::default(),
+        vault_pda_in: Pubkey::default(),
+        vault_pda_out: Pubkey::default(),
+        treasury_pda: Pubkey::default(),
+        treasury_ata_in: Pubkey::default(),
+        treasury_ata_out: Pubkey::default(),
+        associated_token_program: Pubkey::default(),
+        token_program: Pubkey::default(),
+        system_program: Pubkey::default(),
+    }
+    .to_account_metas(None)
+    .len()
+});
 
-pub const MINT_ORACLES: &[(Pubkey, Pubkey)] = &[
-    (SOL_MINT, Pubkey::from_str_const("7UVimffxr9ow1uXYxsr4LHAcV58mLzhmwaeKvJ1pjLiE")),
-    (USDC_MINT, Pubkey::from_str_const("Dpw1EAVrSB1ibxiDQyTAW6Zip3J4Btk2x4SgApQCeFbX")),
+/// Vault mints tracked when the keyed account params do not list any vaults, with the
+/// Pyth feed id expected for each
+///
+/// The program keeps no registry of its vaults and `update` only sees the accounts it
+/// asks for, so vaults of other mints are only routed when listed in the params, along
+/// with their feed ids.
+const DEFAULT_VAULTS: &[(Pubkey, &str)] = &[
+    (
+        SOL_MINT,
+        "0xef0d8b6fda2ceba41da15d4095d1da392a0d2f8ed0c6c7bc0f4cfac8c280b56d",
+    ),
+    (
+        USDC_MINT,
+        "0xeaa020c61cc479712813461ce153894a96a6c00b21ed0cfc2798d1f9a9e9c94a",
+    ),
 ];
 
+/// Params accepted in the keyed account, e.g. `{"vaults": ["<vault pda>", ...]}`
+#[derive(Deserialize, Default, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct OxediumAmmParams {
+    /// Vault PDAs to route through, the SOL and USDC vaults when unset.
+    /// Their mints and oracles are read from the vault state, their feed ids must be
+    /// listed in `feed_ids`
+    #[serde(default)]
+    pub vaults: Option<Vec<String>>,
+    #[serde(default)]
+    pub quote_mode: QuoteMode,
+    /// Refuse to quote when an oracle confidence interval is wider than this, relative to its price
+    #[serde(default)]
+    pub max_confidence_bps: Option<u64>,
+    #[serde(default)]
+    pub update_mode: UpdateMode,
+    /// Oxedium deployment to route through, defaults to the owner of the keyed account
+    #[serde(default)]
+    pub program_id: Option<String>,
+    /// Hex Pyth feed id keyed by mint, added to those of the default vaults when `vaults` is unset
+    #[serde(default)]
+    pub feed_ids: Option<std::collections::HashMap<String, String>>,
+    /// Curve applied to the output vault deviation for the swap fee, quadratic like the program by default
+    #[serde(default)]
+    pub fee_curve: FeeCurveModel,
+}
+
+/// How oracle confidence intervals are used when quoting
+#[derive(Deserialize, Default, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum QuoteMode {
+    /// Quote at the oracle prices, like the on-chain program
+    #[default]
+    Default,
+    /// Price the input at `price - conf` and the output at `price + conf`
+    Conservative,
+}
+
+/// How `update` handles vault, mint, oracle and treasury accounts that fail to decode
+#[derive(Deserialize, Default, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum UpdateMode {
+    /// Keep the last good state of the account and record it in `stale_accounts`
+    #[default]
+    Lenient,
+    /// Fail the update, leaving the AMM state untouched
+    Strict,
+}
+
+pub fn find_vault_pda(mint: &Pubkey, program_id: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[VAULT_SEED.as_bytes(), mint.as_ref()], program_id).0
+}
+
+pub fn find_treasury_pda(program_id: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[OXEDIUM_SEED.as_bytes(), TREASURY_SEED.as_bytes()],
+        program_id,
+    )
+    .0
+}
+
+fn parse_feed_id(feed_id: &str) -> Result<FeedId> {
+    get_feed_id_from_hex(feed_id)
+        .map_err(|e| OxediumError::InvalidParams(format!("Invalid feed id {feed_id}: {e}")).into())
+}
+
+pub fn find_staker_pda(vault: &Pubkey, owner: &Pubkey, program_id: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[STAKER_SEED.as_bytes(), vault.as_ref(), owner.as_ref()],
+        program_id,
+    )
+    .0
+}
+
+/// Anchor account data past its 8 byte discriminator, rejecting an account of another type
+fn account_data<'a>(address: &Pubkey, data: &'a [u8], discriminator: &[u8; 8]) -> Result<&'a [u8]> {
+    let (actual, data) = data
+        .split_at_checked(8)
+        .ok_or_else(|| anyhow::anyhow!("account data is shorter than its discriminator"))?;
+    if actual != discriminator {
+        return Err(OxediumError::InvalidDiscriminator {
+            address: *address,
+            expected: *discriminator,
+            actual: actual.try_into()?,
+        }
+        .into());
+    }
+    Ok(data)
+}
+
+/// Data of an Oxedium program account, rejecting accounts owned by another program
+fn program_account_data<'a>(
+    address: &Pubkey,
+    account: &'a Account,
+    program_id: &Pubkey,
+    discriminator: &[u8; 8],
+) -> Result<&'a [u8]> {
+    if account.owner != *program_id {
+        return Err(OxediumError::InvalidAccountOwner {
+            address: *address,
+            owner: account.owner,
+            program_id: *program_id,
+        }
+        .into());
+    }
+    account_data(address, &account.data, discriminator)
+}
+
+/// Rejects a zero or negative price, which cannot be used to convert amounts
+fn positive_price(price_update: &PriceUpdateV2) -> Result<OraclePrice> {
+    OraclePrice::from_price_message(&price_update.price_message).ok_or_else(|| {
+        OxediumError::NonPositiveOraclePrice {
+            price: price_update.price_message.price,
+        }
+        .into()
+    })
+}
+
+/// Rejects a price whose confidence interval is wider than allowed
+fn ensure_narrow_confidence(
+    oracle: &Pubkey,
+    price: OraclePrice,
+    conf: u64,
+    max_confidence_bps: Option<u64>,
+) -> Result<()> {
+    let Some(max_confidence_bps) = max_confidence_bps else {
+        return Ok(());
+    };
+    let confidence_bps = price.confidence_bps(conf);
+    if confidence_bps > max_confidence_bps {
+        return Err(OxediumError::OracleConfidenceTooWide {
+            oracle: *oracle,
+            confidence_bps,
+            max_confidence_bps,
+        }
+        .into());
+    }
+    Ok(())
+}
+
+/// Rejects a price update that is not fully verified or is not the feed of the vault mint,
+/// mints without a known feed id are only checked for verification
+fn ensure_verified_feed(
+    oracle: &Pubkey,
+    price_update: &PriceUpdateV2,
+    mint: &Pubkey,
+    expected_feed_id: Option<&FeedId>,
+) -> Result<()> {
+    if !matches!(price_update.verification_level, VerificationLevel::Full) {
+        return Err(OxediumError::OracleNotFullyVerified { oracle: *oracle }.into());
+    }
+    if let Some(expected_feed_id) = expected_feed_id {
+        if price_update.price_message.feed_id != *expected_feed_id {
+            return Err(OxediumError::OracleFeedIdMismatch {
+                oracle: *oracle,
+                mint: *mint,
+                expected: *expected_feed_id,
+                actual: price_update.price_message.feed_id,
+            }
+            .into());
+        }
+    }
+    Ok(())
+}
+
+/// Rejects a price the on-chain program would consider too old for the vault
+fn ensure_fresh_price(
+    oracle: &Pubkey,
+    price_update: &PriceUpdateV2,
+    max_age: u64,
+    unix_timestamp: i64,
+) -> Result<()> {
+    let age = unix_timestamp.saturating_sub(price_update.price_message.publish_time);
+    if i128::from(age) > i128::from(max_age) {
+        return Err(OxediumError::StaleOracle {
+            oracle: *oracle,
+            age,
+            max_age,
+        }
+        .into());
+    }
+    Ok(())
+}
+
+/// Rejects a mint `oxedium_swap` cannot move: the program transfers with
+/// `anchor_spl::token::transfer`, which only targets the SPL Token program
+fn ensure_supported_token_program(mint: &Pubkey, mint_info: &MintInfo) -> Result<()> {
+    if mint_info.token_program != spl_token::ID {
+        return Err(OxediumError::UnsupportedTokenProgram {
+            mint: *mint,
+            token_program: mint_info.token_program,
+        }
+        .into());
+    }
+    Ok(())
+}
+
+/// Breakdown of an Oxedium quote, amounts are in smallest units
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OxediumQuoteDetails {
+    pub in_amount: u64,
+    /// Amount received by the user, after swap fees
+    pub out_amount: u64,
+    /// LP fee set by the vault liquidity deviation
+    pub swap_fee_bps: u64,
+    /// Treasury protocol fee
+    pub protocol_fee_bps: u64,
+    /// Output amount at the oracle prices, before any fee
+    pub raw_amount_out: u64,
+    pub lp_fee_amount: u64,
+    pub protocol_fee_amount: u64,
+    /// Prices used for the quote, confidence adjusted in `QuoteMode::Conservative`
+    pub price_in: OraclePrice,
+    pub price_out: OraclePrice,
+    /// Deviation of the output vault liquidity from its initial liquidity
+    pub deviation_bps: u64,
+}
+
+#[derive(Clone)]
 pub struct OxediumAmm {
     pub key: Pubkey,
     pub label: String,
+    /// Vault PDAs listed in the keyed account params, or those of `DEFAULT_VAULTS`
+    pub vault_addresses: Vec<Pubkey>,
+    /// Vault state keyed by the vault token mint
     pub vaults: HashMap<Pubkey, Vault>,
-    pub mints: HashMap<Pubkey, Mint>,
+    /// Vault PDA keyed by the vault token mint, recorded when the vault is loaded
+    pub vault_pdas: HashMap<Pubkey, Pubkey>,
+    pub mints: HashMap<Pubkey, MintInfo>,
     pub oracles: HashMap<Pubkey, PriceUpdateV2>,
     pub treasury: Treasury,
+    pub treasury_pda: Pubkey,
+    /// Treasury ATA keyed by mint, derived once with the mint token program
+    pub treasury_atas: HashMap<Pubkey, Pubkey>,
     pub program_id: Pubkey,
+    pub clock_ref: ClockRef,
+    pub quote_mode: QuoteMode,
+    pub max_confidence_bps: Option<u64>,
+    pub update_mode: UpdateMode,
+    /// Expected Pyth feed id keyed by mint
+    pub feed_ids: HashMap<Pubkey, FeedId>,
+    /// Vault mints without an expected feed id, their oracles are only checked for
+    /// verification. A strict update fails on them instead, add them to the `feedIds`
+    /// params to check their feed
+    pub mints_without_feed_id: HashSet<Pubkey>,
+    pub fee_curve: FeeCurveModel,
+    /// Accounts whose last update failed to decode, their previous state is still in use
+    pub stale_accounts: HashSet<Pubkey>,
+}
+
+impl OxediumAmm {
+    /// Registers an extra Oxedium deployment, e.g. a staging or local validator program id,
+    /// so that its keyed accounts are loaded as `OxediumAmm` under `label`
+    pub fn register_program_id(program_id: Pubkey, label: &'static str) {
+        register_program_id::<OxediumAmm>(program_id, label);
+    }
+
+    /// Quotes a swap like `Amm::quote`, keeping the fee breakdown, the oracle prices
+    /// and the output vault deviation that the swap fee was derived from
+    pub fn quote_detailed(&self, params: &QuoteParams) -> Result<OxediumQuoteDetails> {
+        let vault_in = self
+            .vaults
+            .get(&params.input_mint)
+            .ok_or(OxediumError::VaultNotFound {
+                mint: params.input_mint,
+            })?;
+
+        let vault_out =
+            self.vaults
+                .get(&params.output_mint)
+                .ok_or(OxediumError::VaultNotFound {
+                    mint: params.output_mint,
+                })?;
+
+        for vault in [vault_in, vault_out] {
+            if !vault.is_active {
+                return Err(OxediumError::VaultInactive {
+                    mint: vault.token_mint,
+                }
+                .into());
+            }
+        }
+
+        let in_mint = self
+            .mints
+            .get(&params.input_mint)
+            .ok_or(OxediumError::MintNotFound {
+                mint: params.input_mint,
+            })?;
+        let out_mint = self
+            .mints
+            .get(&params.output_mint)
+            .ok_or(OxediumError::MintNotFound {
+                mint: params.output_mint,
+            })?;
+        // The swap could not be executed, whatever the amounts
+        ensure_supported_token_program(&params.input_mint, in_mint)?;
+        ensure_supported_token_program(&params.output_mint, out_mint)?;
+
+        let in_decimals = in_mint.decimals as u32;
+        let out_decimals = out_mint.decimals as u32;
+
+        let price_in_data =
+            self.oracles
+                .get(&vault_in.pyth_price_account)
+                .ok_or(OxediumError::OracleNotFound {
+                    oracle: vault_in.pyth_price_account,
+                })?;
+        let price_out_data = self.oracles.get(&vault_out.pyth_price_account).ok_or(
+            OxediumError::OracleNotFound {
+                oracle: vault_out.pyth_price_account,
+            },
+        )?;
+
+        ensure_verified_feed(
+            &vault_in.pyth_price_account,
+            price_in_data,
+            &params.input_mint,
+            self.feed_ids.get(&params.input_mint),
+        )?;
+        ensure_verified_feed(
+            &vault_out.pyth_price_account,
+            price_out_data,
+            &params.output_mint,
+            self.feed_ids.get(&params.output_mint),
+        )?;
+
+        let unix_timestamp = self.clock_ref.unix_timestamp.load(Ordering::Relaxed);
+        ensure_fresh_price(
+            &vault_in.pyth_price_account,
+            price_in_data,
+            vault_in.max_age_price,
+            unix_timestamp,
+        )?;
+        ensure_fresh_price(
+            &vault_out.pyth_price_account,
+            price_out_data,
+            vault_out.max_age_price,
+            unix_timestamp,
+        )?;
+
+        let price_in = positive_price(price_in_data)?;
+        let price_out = positive_price(price_out_data)?;
+
+        ensure_narrow_confidence(
+            &vault_in.pyth_price_account,
+            price_in,
+            price_in_data.price_message.conf,
+            self.max_confidence_bps,
+        )?;
+        ensure_narrow_confidence(
+            &vault_out.pyth_price_account,
+            price_out,
+            price_out_data.price_message.conf,
+            self.max_confidence_bps,
+        )?;
+
+        let (price_in, price_out) = match self.quote_mode {
+            QuoteMode::Default => (price_in, price_out),
+            QuoteMode::Conservative => (
+                price_in.lower_bound(price_in_data.price_message.conf),
+                price_out.upper_bound(price_out_data.price_message.conf),
+            ),
+        };
+
+        let (in_amount, result) = match params.swap_mode {
+            SwapMode::ExactIn => {
+                let result = compute_swap_math(
+                    params.amount,
+                    price_in,
+                    price_out,
+                    in_decimals,
+                    out_decimals,
+                    vault_in,
+                    vault_out,
+                    self.treasury.fee_bps,
+                    &self.fee_curve,
+                )?;
+                (params.amount, result)
+            }
+            SwapMode::ExactOut => compute_swap_math_exact_out(
+                params.amount,
+                price_in,
+                price_out,
+                in_decimals,
+                out_decimals,
+                vault_in,
+                vault_out,
+                self.treasury.fee_bps,
+                &self.fee_curve,
+            )?,
+        };
+
+        Ok(OxediumQuoteDetails {
+            in_amount,
+            out_amount: result.net_amount_out,
+            swap_fee_bps: result.swap_fee_bps,
+            protocol_fee_bps: self.treasury.fee_bps,
+            raw_amount_out: result.raw_amount_out,
+            lp_fee_amount: result.lp_fee_amount,
+            protocol_fee_amount: result.protocol_fee_amount,
+            price_in,
+            price_out,
+            deviation_bps: vault_deviation_bps(vault_out),
+        })
+    }
+
+    /// Quotes a swap and returns a copy of the AMM with the swap applied to its vaults,
+    /// to simulate sequential swaps against the updated imbalance fee
+    ///
+    /// Mirrors the vault updates of `oxedium_swap`:
+    /// - the input vault liquidity grows by the gross `details.in_amount`, as the program
+    ///   adds the instruction amount unchanged: both fees are taken from the output side
+    /// - the output vault liquidity only drops by the net output, so both fees stay in the
+    ///   output vault liquidity. The LP fee is credited to the output vault
+    ///   `cumulative_yield_per_lp`, pro rata to its initial liquidity, and the protocol fee
+    ///   to its `protocol_yield`
+    pub fn apply_swap(&self, params: &QuoteParams) -> Result<(OxediumQuoteDetails, OxediumAmm)> {
+        let details = self.quote_detailed(params)?;
+        let net_amount_out =
+            details.raw_amount_out - details.lp_fee_amount - details.protocol_fee_amount;
+
+        let mut amm = self.clone();
+        let vault_in =
+            amm.vaults
+                .get_mut(&params.input_mint)
+                .ok_or(OxediumError::VaultNotFound {
+                    mint: params.input_mint,
+                })?;
+        vault_in.current_liquidity = vault_in
+            .current_liquidity
+            .checked_add(details.in_amount)
+            .ok_or(OxediumError::MathOverflow("input vault liquidity"))?;
+
+        let vault_out =
+            amm.vaults
+                .get_mut(&params.output_mint)
+                .ok_or(OxediumError::VaultNotFound {
+                    mint: params.output_mint,
+                })?;
+        vault_out.current_liquidity = vault_out
+            .current_liquidity
+            .checked_sub(net_amount_out)
+            .ok_or(OxediumError::MathOverflow("output vault liquidity"))?;
+        let lp_fee_per_lp = (details.lp_fee_amount as u128 * SCALE)
+            .checked_div(vault_out.initial_liquidity as u128)
+            .ok_or(OxediumError::NoInitialLiquidity {
+                mint: params.output_mint,
+            })?;
+        vault_out.cumulative_yield_per_lp = vault_out
+            .cumulative_yield_per_lp
+            .checked_add(lp_fee_per_lp)
+            .ok_or(OxediumError::MathOverflow("cumulative yield per LP"))?;
+        vault_out.protocol_yield = vault_out
+            .protocol_yield
+            .checked_add(details.protocol_fee_amount)
+            .ok_or(OxediumError::MathOverflow("protocol yield"))?;
+
+        Ok((details, amm))
+    }
+
+    /// Largest exact in amount accepted for a mint pair, and its quote
+    ///
+    /// Inputs with a raw output of at most one unit are dust below the minimum: both fees
+    /// round up to at least one unit, so they can fail the fee calculation on a pair that
+    /// trades larger amounts. The input is doubled until it clears the dust. Above it the
+    /// swap fee of a pair only depends on the vault state, so every bound on the input
+    /// (output vault liquidity, input vault capacity, math overflow) is monotonic and the
+    /// largest input is found with a binary search up to `u64::MAX`.
+    /// Fails with the quote error when no input above the dust can be swapped, e.g. when
+    /// the vault is inactive or the total fee exceeds 100%.
+    pub fn max_tradable_amount(
+        &self,
+        input_mint: Pubkey,
+        output_mint: Pubkey,
+    ) -> Result<OxediumQuoteDetails> {
+        let quote = |amount: u64| {
+            self.quote_detailed(&QuoteParams {
+                amount,
+                input_mint,
+                output_mint,
+                swap_mode: SwapMode::ExactIn,
+            })
+        };
+
+        let mut low: u64 = 1;
+        let mut details = loop {
+            let result = quote(low);
+            let is_dust = match &result {
+                Ok(details) => details.raw_amount_out <= 1,
+                Err(error) => matches!(
+                    error.downcast_ref::<OxediumError>(),
+                    Some(OxediumError::FeeOverflow)
+                ),
+            };
+            match low.checked_mul(2) {
+                Some(next) if is_dust => low = next,
+                _ => break result?,
+            }
+        };
+        if let Ok(max_details) = quote(u64::MAX) {
+            return Ok(max_details);
+        }
+
+        // `low` always quotes, `high` never does
+        let mut high = u64::MAX;
+        while high - low > 1 {
+            let mid = low + (high - low) / 2;
+            match quote(mid) {
+                Ok(mid_details) => {
+                    low = mid;
+                    details = mid_details;
+                }
+                Err(_) => high = mid,
+            }
+        }
+
+        Ok(details)
+    }
+
+    /// Values the LP position recorded in a staker account of one of the tracked vaults
+    ///
+    /// `lp_balance` is the LP token balance of the staker owner, the staker account
+    /// does not record it.
+    pub fn staker_position(
+        &self,
+        staker_address: &Pubkey,
+        staker_account: &Account,
+        lp_balance: u64,
+    ) -> Result<StakerPosition> {
+        let staker = program_account_data(
+            staker_address,
+            staker_account,
+            &self.program_id,
+            &STAKER_DISCRIMINATOR,
+        )
+        .and_then(|mut data| Ok(Staker::deserialize(&mut data)?))
+        .map_err(|e| OxediumError::InvalidAccount {
+            address: *staker_address,
+            reason: e.to_string(),
+        })?;
+
+        let (vault, vault_pda) = self
+            .vaults
+            .get(&staker.vault_mint)
+            .zip(self.vault_pdas.get(&staker.vault_mint))
+            .ok_or(OxediumError::StakerVaultNotFound {
+                mint: staker.vault_mint,
+            })?;
+
+        // The account must be the staker PDA of the owner it records in that vault
+        let expected = find_staker_pda(vault_pda, &staker.owner, &self.program_id);
+        if *staker_address != expected {
+            return Err(OxediumError::StakerAddressMismatch {
+                address: *staker_address,
+                expected,
+            }
+            .into());
+        }
+
+        staker_position(vault, &staker, lp_balance)
+    }
 }
 
 impl AmmProgramIdToLabel for OxediumAmm {
@@ -64,7 +667,22 @@ impl Amm for OxediumAmm {
     }
 
     fn get_reserve_mints(&self) -> Vec<Pubkey> {
-        ALL_POSSIBLE_MINTS.to_vec()
+        // Inactive vaults and Token-2022 mints cannot be swapped through,
+        // sorted so that mint permutations are deterministic
+        let mut mints = self
+            .vaults
+            .iter()
+            .filter(|(mint, vault)| {
+                vault.is_active
+                    && self
+                        .mints
+                        .get(*mint)
+                        .is_none_or(|mint_info| mint_info.token_program == spl_token::ID)
+            })
+            .map(|(mint, _)| *mint)
+            .collect::<Vec<_>>();
+        mints.sort();
+        mints
     }
 
     fn has_dynamic_accounts(&self) -> bool {
@@ -72,11 +690,11 @@ impl Amm for OxediumAmm {
     }
 
     fn requires_update_for_reserve_mints(&self) -> bool {
-        false
+        true
     }
 
     fn supports_exact_out(&self) -> bool {
-        false
+        true
     }
 
     fn unidirectional(&self) -> bool {
@@ -88,153 +706,266 @@ impl Amm for OxediumAmm {
     }
 
     fn get_accounts_len(&self) -> usize {
-        15
+        *OXEDIUM_SWAP_ACCOUNTS_LEN
     }
 
     fn underlying_liquidities(&self) -> Option<HashSet<Pubkey>> {
-        None
+        // Every pair market draws on the same vaults, so routes must not split across them.
+        // Only loaded vaults are routed, configured vaults missing on chain are left out
+        Some(self.vault_pdas.values().copied().collect())
     }
 
     fn is_active(&self) -> bool {
-        true
+        // Swaps are paused program wide while the treasury stoptap is set
+        !self.treasury.stoptap
     }
 
-    fn from_keyed_account(keyed: &KeyedAccount, _ctx: &AmmContext) -> Result<Self> {
+    fn from_keyed_account(keyed: &KeyedAccount, ctx: &AmmContext) -> Result<Self> {
+        let params: OxediumAmmParams = match &keyed.params {
+            Some(params) => serde_json::from_value(params.clone())
+                .map_err(|e| OxediumError::InvalidParams(e.to_string()))?,
+            None => OxediumAmmParams::default(),
+        };
+
+        let program_id = match &params.program_id {
+            Some(program_id) => Pubkey::from_str(program_id).map_err(|e| {
+                OxediumError::InvalidParams(format!("Invalid program id {program_id}: {e}"))
+            })?,
+            None => keyed.account.owner,
+        };
+        let label = program_id_label(&program_id).unwrap_or("Oxedium");
+        let vault_addresses = match &params.vaults {
+            Some(vaults) => vaults
+                .iter()
+                .map(|vault| {
+                    Pubkey::from_str(vault)
+                        .map_err(|e| OxediumError::InvalidVaultAddress(format!("{vault}: {e}")))
+                })
+                .collect::<std::result::Result<Vec<_>, _>>()?,
+            None => DEFAULT_VAULTS
+                .iter()
+                .map(|(mint, _)| find_vault_pda(mint, &program_id))
+                .collect(),
+        };
+
+        let mut feed_ids = HashMap::default();
+        if params.vaults.is_none() {
+            for (mint, feed_id) in DEFAULT_VAULTS {
+                feed_ids.insert(*mint, parse_feed_id(feed_id)?);
+            }
+        }
+        for (mint, feed_id) in params.feed_ids.iter().flatten() {
+            let mint = Pubkey::from_str(mint).map_err(|e| {
+                OxediumError::InvalidParams(format!("Invalid feed id mint {mint}: {e}"))
+            })?;
+            feed_ids.insert(mint, parse_feed_id(feed_id)?);
+        }
+
         Ok(Self {
             key: keyed.key,
-            label: "Oxedium".to_string(),
-            program_id: OXEDIUM_PROGRAM_ID,
+            label: label.to_string(),
+            program_id,
+            vault_addresses,
             vaults: Default::default(),
+            vault_pdas: Default::default(),
             mints: Default::default(),
             oracles: Default::default(),
             treasury: Default::default(),
+            treasury_pda: find_treasury_pda(&program_id),
+            treasury_atas: Default::default(),
+            clock_ref: ctx.clock_ref.clone(),
+            quote_mode: params.quote_mode,
+            max_confidence_bps: params.max_confidence_bps,
+            update_mode: params.update_mode,
+            feed_ids,
+            mints_without_feed_id: Default::default(),
+            fee_curve: params.fee_curve,
+            stale_accounts: Default::default(),
         })
     }
 
     fn get_accounts_to_update(&self) -> Vec<Pubkey> {
-        let mut accounts = vec![];
-
-        for mint in ALL_POSSIBLE_MINTS {
-            let vault = Pubkey::find_program_address(
-                &[VAULT_SEED.as_bytes(), mint.as_ref()],
-                &self.program_id,
-            )
-            .0;
-            accounts.push(vault);
-            accounts.push(*mint);
-
-            if let Some((_, oracle)) = MINT_ORACLES.iter().find(|(m, _)| m == mint) {
-                accounts.push(*oracle);
-            }
+        let mut accounts = vec![self.treasury_pda];
+        accounts.extend(&self.vault_addresses);
+
+        // Mints and oracles are only known once the vaults have been loaded
+        for vault in self.vaults.values() {
+            accounts.push(vault.token_mint);
+            accounts.push(vault.pyth_price_account);
         }
 
         accounts
     }
 
     fn update(&mut self, account_map: &AccountMap) -> Result<()> {
-        for mint in ALL_POSSIBLE_MINTS {
-            // Vault PDA
-            let vault_pda = Pubkey::find_program_address(
-                &[VAULT_SEED.as_bytes(), mint.as_ref()],
-                &self.program_id,
-            )
-            .0;
-
-            if let Some(vault_account) = account_map.get(&vault_pda) {
-
-                if vault_account.data.len() >= std::mem::size_of::<Vault>() {
-                    if let Ok(vault) = Vault::deserialize(&mut &vault_account.data[8..]) {
-                        self.vaults.insert(*mint, vault);
-                    } else {
-                        println!(">>> warning: failed to deserialize vault {:?}", vault_pda);
-                    }
-                } else {
-                    println!(">>> warning: vault account data too small {:?}", vault_pda);
+        // Decode into copies so a strict update failure leaves the current state untouched
+        let mut treasury = self.treasury.clone();
+        let mut vaults = self.vaults.clone();
+        let mut vault_pdas = self.vault_pdas.clone();
+        let mut mints = self.mints.clone();
+        let mut treasury_atas = self.treasury_atas.clone();
+        let mut oracles = self.oracles.clone();
+        let mut stale_accounts = self.stale_accounts.clone();
+        let mut record = |address: Pubkey, decoded: Result<()>| -> Result<()> {
+            match decoded {
+                Ok(()) => {
+                    stale_accounts.remove(&address);
+                    Ok(())
                 }
-            }
-
-            if let Some(mint_account) = account_map.get(mint) {
-                if mint_account.data.len() >= spl_token::state::Mint::LEN {
-                    if let Ok(mint_data) = Mint::unpack(&mint_account.data) {
-                        self.mints.insert(*mint, mint_data);
-                    } else {
-                        println!(">>> warning: failed to unpack mint {:?}", mint);
+                Err(e) => {
+                    log::warn!("Failed to decode Oxedium account {address}: {e}");
+                    match self.update_mode {
+                        UpdateMode::Strict => Err(OxediumError::InvalidAccount {
+                            address,
+                            reason: e.to_string(),
+                        }
+                        .into()),
+                        UpdateMode::Lenient => {
+                            stale_accounts.insert(address);
+                            Ok(())
+                        }
                     }
-                } else {
-                    println!(">>> warning: mint account data too small {:?}", mint);
                 }
             }
+        };
+
+        if let Some(treasury_account) = account_map.get(&self.treasury_pda) {
+            record(
+                self.treasury_pda,
+                program_account_data(
+                    &self.treasury_pda,
+                    treasury_account,
+                    &self.program_id,
+                    &TREASURY_DISCRIMINATOR,
+                )
+                .and_then(|mut data| Ok(Treasury::deserialize(&mut data)?))
+                .map(|decoded| treasury = decoded),
+            )?;
         }
 
-        for vault in self.vaults.values() {
-            if let Some(oracle_account) = account_map.get(&vault.pyth_price_account) {
-                if let Ok(price_data) = PriceUpdateV2::deserialize(&mut &oracle_account.data[8..]) {
-                    self.oracles.insert(vault.pyth_price_account, price_data);
-                } else {
-                    println!(
-                        ">>> warning: failed to deserialize oracle {:?}",
-                        vault.pyth_price_account
-                    );
-                }
+        for vault_pda in &self.vault_addresses {
+            if let Some(vault_account) = account_map.get(vault_pda) {
+                record(
+                    *vault_pda,
+                    program_account_data(
+                        vault_pda,
+                        vault_account,
+                        &self.program_id,
+                        &VAULT_DISCRIMINATOR,
+                    )
+                    .and_then(|mut data| Ok(Vault::deserialize(&mut data)?))
+                    .map(|vault| {
+                        vault_pdas.insert(vault.token_mint, *vault_pda);
+                        vaults.insert(vault.token_mint, vault);
+                    }),
+                )?;
             }
         }
-        Ok(())
-    }
 
-    fn quote(&self, params: &QuoteParams) -> Result<Quote> {
-
-        let vault_in = self
-            .vaults
-            .get(&params.input_mint)
-            .ok_or_else(|| anyhow::anyhow!("Vault for input mint not found"))?;
+        let mints_without_feed_id = vaults
+            .keys()
+            .filter(|mint| !self.feed_ids.contains_key(mint))
+            .copied()
+            .collect::<HashSet<_>>();
+        if self.update_mode == UpdateMode::Strict {
+            if let Some(mint) = mints_without_feed_id.iter().min() {
+                return Err(OxediumError::MissingFeedId { mint: *mint }.into());
+            }
+        }
+        for mint in mints_without_feed_id.difference(&self.mints_without_feed_id) {
+            log::warn!(
+                "No Pyth feed id for Oxedium vault mint {mint}, its oracle feed is not checked"
+            );
+        }
 
-        let vault_out = self
-            .vaults
-            .get(&params.output_mint)
-            .ok_or_else(|| anyhow::anyhow!("Vault for output mint not found"))?;
+        for mint in vaults.keys() {
+            if let Some(mint_account) = account_map.get(mint) {
+                record(
+                    *mint,
+                    parse_mint_info(mint_account).map(|mint_info| {
+                        mints.insert(*mint, mint_info);
+                    }),
+                )?;
+            }
+        }
 
-        let in_mint = self
-            .mints
-            .get(&params.input_mint)
-            .ok_or_else(|| anyhow::anyhow!("Mint info for input not found"))?;
-        let out_mint = self
-            .mints
-            .get(&params.output_mint)
-            .ok_or_else(|| anyhow::anyhow!("Mint info for output not found"))?;
+        for (mint, mint_info) in &mints {
+            treasury_atas.entry(*mint).or_insert_with(|| {
+                get_associated_token_address_with_program_id(
+                    &self.treasury_pda,
+                    mint,
+                    &mint_info.token_program,
+                )
+            });
+        }
 
-        let in_decimals = in_mint.decimals as u32;
-        let out_decimals = out_mint.decimals as u32;
+        for vault in vaults.values() {
+            if let Some(oracle_account) = account_map.get(&vault.pyth_price_account) {
+                let decoded: Result<PriceUpdateV2> =
+                    if oracle_account.owner != pyth_solana_receiver_sdk::ID {
+                        Err(OxediumError::InvalidOracleOwner {
+                            oracle: vault.pyth_price_account,
+                            owner: oracle_account.owner,
+                        }
+                        .into())
+                    } else {
+                        account_data(
+                            &vault.pyth_price_account,
+                            &oracle_account.data,
+                            &PRICE_UPDATE_V2_DISCRIMINATOR,
+                        )
+                        .and_then(|mut data| Ok(PriceUpdateV2::deserialize(&mut data)?))
+                    };
+                record(
+                    vault.pyth_price_account,
+                    decoded
+                        .and_then(|price_update| {
+                            ensure_verified_feed(
+                                &vault.pyth_price_account,
+                                &price_update,
+                                &vault.token_mint,
+                                self.feed_ids.get(&vault.token_mint),
+                            )?;
+                            positive_price(&price_update)?;
+                            Ok(price_update)
+                        })
+                        .map(|price_update| {
+                            oracles.insert(vault.pyth_price_account, price_update);
+                        }),
+                )?;
+            }
+        }
 
-        let price_in_data = self
-            .oracles
-            .get(&vault_in.pyth_price_account)
-            .ok_or_else(|| anyhow::anyhow!("Oracle for input mint not found"))?;
-        let price_out_data = self
-            .oracles
-            .get(&vault_out.pyth_price_account)
-            .ok_or_else(|| anyhow::anyhow!("Oracle for output mint not found"))?;
+        if !stale_accounts.is_empty() {
+            log::debug!("Oxedium accounts kept at their last good state: {stale_accounts:?}");
+        }
 
-        let price_in = price_in_data.price_message.price as u64;
-        let price_out = price_out_data.price_message.price as u64;
+        self.treasury = treasury;
+        self.vaults = vaults;
+        self.vault_pdas = vault_pdas;
+        self.mints = mints;
+        self.treasury_atas = treasury_atas;
+        self.oracles = oracles;
+        self.mints_without_feed_id = mints_without_feed_id;
+        self.stale_accounts = stale_accounts;
+        Ok(())
+    }
 
-        let result = compute_swap_math(
-            params.amount,
-            price_in,
-            price_out,
-            in_decimals,
-            out_decimals,
-            vault_in,
-            vault_out,
-            0,
-        )?;
+    fn quote(&self, params: &QuoteParams) -> Result<Quote> {
+        let details = self.quote_detailed(params)?;
 
-        let total_fee = result.lp_fee_amount + result.protocol_fee_amount;
+        let total_fee = details.lp_fee_amount + details.protocol_fee_amount;
 
-        let fee_pct = Decimal::from(total_fee) / Decimal::from(params.amount);
+        // Fees are charged in the output mint, relative to the output before fees
+        let fee_pct = if details.raw_amount_out == 0 {
+            Decimal::ZERO
+        } else {
+            Decimal::from(total_fee) / Decimal::from(details.raw_amount_out)
+        };
 
         Ok(Quote {
-            in_amount: params.amount,
-            out_amount: result.net_amount_out,
+            in_amount: details.in_amount,
+            out_amount: details.out_amount,
             fee_amount: total_fee,
             fee_mint: params.output_mint,
             fee_pct,
@@ -245,67 +976,88 @@ impl Amm for OxediumAmm {
         let vault_in = self
             .vaults
             .get(&params.source_mint)
-            .ok_or_else(|| anyhow::anyhow!("Vault for source mint not found"))?;
-        let vault_out = self
-            .vaults
-            .get(&params.destination_mint)
-            .ok_or_else(|| anyhow::anyhow!("Vault for destination mint not found"))?;
+            .ok_or(OxediumError::VaultNotFound {
+                mint: params.source_mint,
+            })?;
+        let vault_out =
+            self.vaults
+                .get(&params.destination_mint)
+                .ok_or(OxediumError::VaultNotFound {
+                    mint: params.destination_mint,
+                })?;
 
-        let vault_in_pda = Pubkey::find_program_address(
-            &[VAULT_SEED.as_bytes(), params.source_mint.as_ref()],
-            &self.program_id,
-        )
-        .0;
-        let vault_out_pda = Pubkey::find_program_address(
-            &[VAULT_SEED.as_bytes(), params.destination_mint.as_ref()],
-            &self.program_id,
-        )
-        .0;
+        let vault_in_pda =
+            self.vault_pdas
+                .get(&params.source_mint)
+                .ok_or(OxediumError::VaultNotFound {
+                    mint: params.source_mint,
+                })?;
+        let vault_out_pda =
+            self.vault_pdas
+                .get(&params.destination_mint)
+                .ok_or(OxediumError::VaultNotFound {
+                    mint: params.destination_mint,
+                })?;
 
-        let treasury_pda = Pubkey::find_program_address(
-            &[OXEDIUM_SEED.as_bytes(), TREASURY_SEED.as_bytes()],
-            &self.program_id,
-        )
-        .0;
-        let treasury_source_ata = get_associated_token_address(&treasury_pda, &params.source_mint);
-        let treasury_destination_ata = get_associated_token_address(&treasury_pda, &params.destination_mint);
+        let source_mint =
+            self.mints
+                .get(&params.source_mint)
+                .ok_or(OxediumError::MintNotFound {
+                    mint: params.source_mint,
+                })?;
+        let destination_mint =
+            self.mints
+                .get(&params.destination_mint)
+                .ok_or(OxediumError::MintNotFound {
+                    mint: params.destination_mint,
+                })?;
+        ensure_supported_token_program(&params.source_mint, source_mint)?;
+        ensure_supported_token_program(&params.destination_mint, destination_mint)?;
+
+        let treasury_source_ata =
+            self.treasury_atas
+                .get(&params.source_mint)
+                .ok_or(OxediumError::MintNotFound {
+                    mint: params.source_mint,
+                })?;
+        let treasury_destination_ata =
+            self.treasury_atas
+                .get(&params.destination_mint)
+                .ok_or(OxediumError::MintNotFound {
+                    mint: params.destination_mint,
+                })?;
 
         let oracle_in = vault_in.pyth_price_account;
         let oracle_out = vault_out.pyth_price_account;
 
-        let metas = vec![
-            AccountMeta::new(params.token_transfer_authority, true),
-            AccountMeta::new(params.source_mint, false),
-            AccountMeta::new(params.destination_mint, false),
-            AccountMeta::new(oracle_in, false),
-            AccountMeta::new(oracle_out, false),
-            AccountMeta::new(params.source_token_account, false),
-            AccountMeta::new(params.destination_token_account, false),
-            AccountMeta::new(vault_in_pda, false),
-            AccountMeta::new(vault_out_pda, false),
-            AccountMeta::new(treasury_pda, false),
-            AccountMeta::new(treasury_source_ata, false),
-            AccountMeta::new(treasury_destination_ata, false),
-            AccountMeta::new_readonly(spl_associated_token_account::ID, false),
-            AccountMeta::new_readonly(spl_token::ID, false),
-            AccountMeta::new_readonly(system_program::ID, false),
-        ];
+        // oxedium_swap takes the same accounts for ExactIn and ExactOut,
+        // the swap mode only changes the amounts passed by the aggregator
+        let account_metas = OxediumSwap {
+            signer: params.token_transfer_authority,
+            mint_in: params.source_mint,
+            mint_out: params.destination_mint,
+            pyth_price_account_in: oracle_in,
+            pyth_price_account_out: oracle_out,
+            signer_ata_in: params.source_token_account,
+            signer_ata_out: params.destination_token_account,
+            vault_pda_in: *vault_in_pda,
+            vault_pda_out: *vault_out_pda,
+            treasury_pda: self.treasury_pda,
+            treasury_ata_in: *treasury_source_ata,
+            treasury_ata_out: *treasury_destination_ata,
+            associated_token_program: spl_associated_token_account::ID,
+            token_program: spl_token::ID,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None);
 
         Ok(SwapAndAccountMetas {
             swap: Swap::Oxedium,
-            account_metas: metas,
+            account_metas,
         })
     }
 
     fn clone_amm(&self) -> Box<dyn Amm + Send + Sync> {
-        Box::new(Self {
-            key: self.key,
-            label: self.label.clone(),
-            program_id: self.program_id,
-            vaults: self.vaults.clone(),
-            mints: self.mints.clone(),
-            oracles: self.oracles.clone(),
-            treasury: self.treasury.clone(),
-        })
+        Box::new(self.clone())
     }
 }
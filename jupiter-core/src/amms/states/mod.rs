@@ -1,5 +1,7 @@
+pub use staker::*;
 pub use treasury::*;
 pub use vault::*;
 
+pub mod staker;
 pub mod treasury;
 pub mod vault;
\ No newline at end of file
@@ -0,0 +1,18 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_sdk::pubkey::Pubkey;
+
+/// LP position of an owner in a vault, at the PDA of `[STAKER_SEED, vault PDA, owner]`
+///
+/// LP tokens themselves live in the owner token account for `Vault::lp_mint`,
+/// the staker only checkpoints the yield accrued by the position.
+#[derive(BorshDeserialize, BorshSerialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Staker {
+    pub owner: Pubkey,
+    /// Token mint of the vault, not the vault PDA
+    pub vault_mint: Pubkey,
+
+    /// `Vault::cumulative_yield_per_lp` at the last deposit, withdrawal or claim
+    pub last_cumulative_yield: u128,
+    /// Yield settled at the last checkpoint and not claimed yet
+    pub pending_claim: u64
+}
\ No newline at end of file
@@ -1,10 +1,12 @@
 use agave_feature_set::FeatureSet;
 use ahash::HashSet;
+use anchor_lang::{AccountDeserialize, AccountSerialize};
 use anyhow::{Context, Error, Result};
 use assert_matches::assert_matches;
 use glob::glob;
 use jupiter_common::client_extension::get_amm_context;
 use litesvm::{types::TransactionResult, LiteSVM};
+use pyth_solana_receiver_sdk::price_update::PriceUpdateV2;
 use regex::Regex;
 use serde_json::Value;
 use solana_account_decoder::{encode_ui_account, UiAccountEncoding};
@@ -27,7 +29,7 @@ use solana_sdk::{
         self,
         instructions::{BorrowedAccountMeta, BorrowedInstruction},
     },
-    transaction::Transaction,
+    transaction::{Transaction, TransactionError},
 };
 use spl_associated_token_account::get_associated_token_address_with_program_id;
 use spl_token_2022::extension::StateWithExtensions;
@@ -49,7 +51,7 @@ use crate::{
     active_features::MAINNET_ACTIVE_FEATURES,
     aggregator_version::AggregatorVersion,
     amm::*,
-    amms::loader::amm_factory,
+    amms::{errors::OxediumError, loader::amm_factory},
     constants,
     route::route::get_token_mints_permutations,
     route::route_plan_with_metadata::JupiterRoutePlanStep,
@@ -77,8 +79,16 @@ pub static SPL_TOKEN_MINT_TO_IN_AMOUNT: LazyLock<HashMap<Pubkey, u64>> = LazyLoc
     ])
 });
 
-pub static TOKEN2022_MINT_TO_IN_AMOUNT: LazyLock<HashMap<Pubkey, u64>> =
-    LazyLock::new(|| HashMap::from([]));
+/// No snapshot holds a Token-2022 vault: the deployed Oxedium program moves tokens with
+/// `anchor_spl::token::transfer`, which always targets the legacy token program, so
+/// `OxediumAmm` refuses to quote Token-2022 mints
+pub static TOKEN2022_MINT_TO_IN_AMOUNT: LazyLock<HashMap<Pubkey, u64>> = LazyLock::new(|| {
+    HashMap::from([(
+        // PYUSD
+        pubkey!("2b1kV6DkPAnxd5ixfnxCpjxmKwqjjaYmCZfHsFu24GXo"),
+        1_110_000,
+    )])
+});
 
 pub static TOKEN_MINT_TO_IN_AMOUNT: LazyLock<HashMap<Pubkey, u64>> = LazyLock::new(|| {
     let mut m = SPL_TOKEN_MINT_TO_IN_AMOUNT.clone();
@@ -86,8 +96,12 @@ pub static TOKEN_MINT_TO_IN_AMOUNT: LazyLock<HashMap<Pubkey, u64>> = LazyLock::n
     m
 });
 
-pub static TOKEN_MINT_TO_OUT_AMOUNT: LazyLock<HashMap<Pubkey, u64>> =
-    LazyLock::new(|| HashMap::from([]));
+pub static TOKEN_MINT_TO_OUT_AMOUNT: LazyLock<HashMap<Pubkey, u64>> = LazyLock::new(|| {
+    HashMap::from([
+        (spl_token::native_mint::ID, 25_000_000),
+        (constants::USDC_MINT, 1_110_000),
+    ])
+});
 
 pub struct AmmTestSwapParams<'a> {
     pub amm: &'a dyn Amm,
@@ -101,6 +115,13 @@ pub struct AmmTestSwapParams<'a> {
     pub amount: Option<u64>,
 }
 
+/// Token amounts moved by a swap executed in LiteSVM
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SimulatedSwap {
+    pub in_amount: u64,
+    pub out_amount: u64,
+}
+
 pub struct AmmTestHarness {
     pub option: Option<String>,
 }
@@ -181,33 +202,17 @@ impl AmmTestHarnessProgramTest {
         (quote_result, quote_error)
     }
 
-    pub fn assert_quote_matches_simulated_swap(
-        &mut self,
-        AmmTestSwapParams {
-            amm,
-            source_mint,
-            destination_mint,
-            swap_mode,
-            tolerance,
-            use_shared_accounts,
-            expected_error,
-            expect_swap,
-            amount,
-        }: AmmTestSwapParams<'_>,
-    ) {
-        let mut amount = amount.unwrap_or_else(|| match swap_mode {
-            SwapMode::ExactIn => *TOKEN_MINT_TO_IN_AMOUNT.get(source_mint).unwrap(),
-            SwapMode::ExactOut => *TOKEN_MINT_TO_OUT_AMOUNT.get(destination_mint).unwrap(),
-        });
-        let (quote_result, quote_error) = Self::find_viable_quote(
-            amm,
-            swap_mode,
-            &mut amount,
-            source_mint,
-            destination_mint,
-            expected_error.is_some(),
-        );
-
+    /// Build the route instructions swapping `amount` through the amm alone,
+    /// along with the swap leg and the user token accounts it moves
+    fn build_swap_instructions(
+        &self,
+        amm: &dyn Amm,
+        source_mint: &Pubkey,
+        destination_mint: &Pubkey,
+        swap_mode: SwapMode,
+        amount: u64,
+        use_shared_accounts: bool,
+    ) -> (Vec<Instruction>, Swap, Pubkey, Pubkey) {
         let user = self.program_test_user.keypair.pubkey();
         let user_source_token_account = self
             .program_test_user
@@ -269,9 +274,6 @@ impl AmmTestHarnessProgramTest {
             swap,
             account_metas,
         } = amm.get_swap_and_account_metas(&swap_params).unwrap();
-        if let Some(expect_swap) = expect_swap {
-            assert_eq!(swap, expect_swap);
-        }
 
         let SwapAccounts { mut accounts } = build_swap_accounts(
             AGGREGATOR_VERSION,
@@ -303,7 +305,7 @@ impl AmmTestHarnessProgramTest {
         accounts.extend(account_metas);
 
         let route_plan = vec![JupiterRoutePlanStep {
-            swap,
+            swap: swap.clone(),
             percent: Some(100),
             bps: Some(10_000),
             input_index: 0,
@@ -338,6 +340,55 @@ impl AmmTestHarnessProgramTest {
 
         ixs.push(swap_ix);
 
+        (
+            ixs,
+            swap,
+            user_source_token_account,
+            user_destination_token_account,
+        )
+    }
+
+    pub fn assert_quote_matches_simulated_swap(
+        &mut self,
+        AmmTestSwapParams {
+            amm,
+            source_mint,
+            destination_mint,
+            swap_mode,
+            tolerance,
+            use_shared_accounts,
+            expected_error,
+            expect_swap,
+            amount,
+        }: AmmTestSwapParams<'_>,
+    ) {
+        let mut amount = amount.unwrap_or_else(|| match swap_mode {
+            SwapMode::ExactIn => *TOKEN_MINT_TO_IN_AMOUNT.get(source_mint).unwrap(),
+            SwapMode::ExactOut => *TOKEN_MINT_TO_OUT_AMOUNT.get(destination_mint).unwrap(),
+        });
+        let (quote_result, quote_error) = Self::find_viable_quote(
+            amm,
+            swap_mode,
+            &mut amount,
+            source_mint,
+            destination_mint,
+            expected_error.is_some(),
+        );
+
+        let (ixs, swap, user_source_token_account, user_destination_token_account) = self
+            .build_swap_instructions(
+                amm,
+                source_mint,
+                destination_mint,
+                swap_mode,
+                amount,
+                use_shared_accounts,
+            );
+        if let Some(expect_swap) = expect_swap {
+            assert_eq!(swap, expect_swap);
+        }
+        let user = self.program_test_user.keypair.pubkey();
+
         let user_keypair = clone_keypair(&self.program_test_user.keypair);
         let user_before = self.lite_svm.get_balance(&user_keypair.pubkey()).unwrap();
         let source_token_account_before = self.get_token_account(&user_source_token_account);
@@ -364,14 +415,20 @@ impl AmmTestHarnessProgramTest {
 
         let (transaction_metadata, quote) = if let Some(expected_error) = expected_error {
             let quote_error: Error = quote_error.unwrap();
-            match expected_error.downcast_ref::<anchor_lang::error::Error>() {
-                Some(error) => {
+            match (
+                expected_error.downcast_ref::<anchor_lang::error::Error>(),
+                expected_error.downcast_ref::<OxediumError>(),
+            ) {
+                (Some(error), _) => {
                     let quote_error = quote_error
                         .downcast_ref::<anchor_lang::error::Error>()
                         .unwrap();
                     assert_eq!(error, quote_error);
                 }
-                None => {
+                (None, Some(error)) => {
+                    assert_eq!(Some(error), quote_error.downcast_ref::<OxediumError>());
+                }
+                (None, None) => {
                     assert_eq!(expected_error.to_string(), quote_error.to_string());
                 }
             }
@@ -506,6 +563,77 @@ impl AmmTestHarnessProgramTest {
         // insta::assert_debug_snapshot!(test_name, quote);
     }
 
+    /// Quote `amount` and execute the same swap in LiteSVM, without asserting on either.
+    /// Meant for differential tests, the quote is not retried with smaller amounts
+    pub fn quote_and_simulate_swap(
+        &mut self,
+        amm: &dyn Amm,
+        source_mint: &Pubkey,
+        destination_mint: &Pubkey,
+        swap_mode: SwapMode,
+        amount: u64,
+        use_shared_accounts: bool,
+    ) -> (Result<Quote>, Result<SimulatedSwap>) {
+        let quote = amm.quote(&QuoteParams {
+            amount,
+            input_mint: *source_mint,
+            output_mint: *destination_mint,
+            swap_mode,
+        });
+
+        let (ixs, _, user_source_token_account, user_destination_token_account) = self
+            .build_swap_instructions(
+                amm,
+                source_mint,
+                destination_mint,
+                swap_mode,
+                amount,
+                use_shared_accounts,
+            );
+        let simulated_swap = self
+            .execute_user_swap(
+                &ixs,
+                &user_source_token_account,
+                &user_destination_token_account,
+            )
+            .map_err(|error| anyhow::anyhow!("Transaction failed: {error:?}"));
+
+        (quote, simulated_swap)
+    }
+
+    /// Execute `instructions` signed by the test user, e.g. a swap instruction of the
+    /// underlying program built without the aggregator, and return the token amounts
+    /// moved out of and into the user token accounts
+    pub fn execute_user_swap(
+        &mut self,
+        instructions: &[Instruction],
+        user_source_token_account: &Pubkey,
+        user_destination_token_account: &Pubkey,
+    ) -> Result<SimulatedSwap, TransactionError> {
+        let user_keypair = clone_keypair(&self.program_test_user.keypair);
+        let source_token_account_before = self.get_token_account(user_source_token_account);
+        let destination_token_account_before =
+            self.get_token_account(user_destination_token_account);
+        let (transaction_result, _) =
+            self.process_transaction(instructions, Some(&user_keypair.pubkey()), &[&user_keypair]);
+        transaction_result
+            .map_err(|failed_transaction_metadata| failed_transaction_metadata.err)?;
+
+        let source_token_account_after = self.get_token_account(user_source_token_account);
+        let destination_token_account_after =
+            self.get_token_account(user_destination_token_account);
+        Ok(SimulatedSwap {
+            in_amount: source_token_account_before
+                .amount
+                .checked_sub(source_token_account_after.amount)
+                .unwrap(),
+            out_amount: destination_token_account_after
+                .amount
+                .checked_sub(destination_token_account_before.amount)
+                .unwrap(),
+        })
+    }
+
     /// To be used for exotic test setup
     pub fn assert_out_amount_matches_simulated_swap(
         &mut self,
@@ -551,6 +679,15 @@ impl AmmTestHarnessProgramTest {
     pub fn get_user(&self) -> Pubkey {
         self.program_test_user.keypair.pubkey()
     }
+
+    pub fn get_user_ata(&self, mint: &Pubkey) -> Pubkey {
+        self.program_test_user.get_user_ata(mint)
+    }
+
+    /// Account state in LiteSVM, e.g. a program account after a simulated swap
+    pub fn get_account(&self, address: &Pubkey) -> Option<Account> {
+        self.lite_svm.get_account(address)
+    }
 }
 
 /// Find the log from the underlying AMM, which should
@@ -588,6 +725,22 @@ impl ProgramTestUser {
     }
 }
 
+/// Republish every Pyth price update of the account map at `publish_time`,
+/// snapshotted prices are usually older than what programs accept
+pub fn refresh_pyth_price_updates(account_map: &mut AccountMap, publish_time: i64) {
+    for account in account_map.values_mut() {
+        if account.owner != pyth_solana_receiver_sdk::ID {
+            continue;
+        }
+        let mut price_update =
+            PriceUpdateV2::try_deserialize(&mut account.data.as_slice()).unwrap();
+        price_update.price_message.publish_time = publish_time;
+        price_update
+            .try_serialize(&mut account.data.as_mut_slice())
+            .unwrap();
+    }
+}
+
 /// Update AMM with only the accounts it requested,
 /// to avoid relying on side effects
 fn update_amm_precise(amm: &mut dyn Amm, account_map: &AccountMap) -> Result<()> {
@@ -706,6 +859,18 @@ impl AmmTestAccountsSnapshot {
         bincode::deserialize(&account.data).map_err(Into::into)
     }
 
+    /// Moves the snapshot clock, the amm context and LiteSVM are both loaded from it
+    pub fn warp_clock(&mut self, unix_timestamp: i64) -> Result<()> {
+        let mut clock = self.get_clock()?;
+        clock.unix_timestamp = unix_timestamp;
+        let account = self
+            .accounts_snapshot
+            .get_mut(&sysvar::clock::ID)
+            .context("Missing clock account")?;
+        account.data = bincode::serialize(&clock)?;
+        Ok(())
+    }
+
     pub fn update_amm_from_snapshot(&self, amm: &mut dyn Amm) {
         update_amm_precise(amm, &self.accounts_snapshot).unwrap();
     }
@@ -980,7 +1145,7 @@ fn setup_token_accounts(
             ata
         };
 
-        mint_to_ata_with_program_id.insert(*reserve_mint, (ata, spl_token::ID));
+        mint_to_ata_with_program_id.insert(*reserve_mint, (ata, token_program_id));
     }
 
     let latest_blockhash = lite_svm.latest_blockhash();
@@ -6,4 +6,15 @@ pub const STAKER_SEED: &str = "staker-seed";
 pub const OXEDIUM_SEED: &str = "oxedium-seed";
 pub const TREASURY_SEED: &str = "treasury-seed";
 
+/// Below this percentage of its initial liquidity, a vault only pays out
+/// `UNSTAKE_LOW_LIQUIDITY_BPS` of the LP tokens unstaked from it
+pub const UNSTAKE_LOW_LIQUIDITY_PCT: u128 = 50;
+pub const UNSTAKE_LOW_LIQUIDITY_BPS: u128 = 200;
+
+/// Anchor account discriminators, the first 8 bytes of `sha256("account:<Name>")`
+pub const VAULT_DISCRIMINATOR: [u8; 8] = [0xd3, 0x08, 0xe8, 0x2b, 0x02, 0x98, 0x75, 0x77];
+pub const TREASURY_DISCRIMINATOR: [u8; 8] = [0xee, 0xef, 0x7b, 0xee, 0x59, 0x01, 0xa8, 0xfd];
+pub const STAKER_DISCRIMINATOR: [u8; 8] = [0xab, 0xe5, 0xc1, 0x55, 0x43, 0xb1, 0x97, 0x04];
+pub const PRICE_UPDATE_V2_DISCRIMINATOR: [u8; 8] = [0x22, 0xf1, 0x23, 0x63, 0x9d, 0x7e, 0xf4, 0xcd];
+
 pub  const SCALE: u128 = 1_000_000_000_000;
\ No newline at end of file
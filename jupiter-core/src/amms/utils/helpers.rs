@@ -1,24 +1,41 @@
 use anchor_lang::AnchorDeserialize;
-use solana_sdk::{account::Account, program_pack::Pack};
+use solana_sdk::{account::Account, program_pack::Pack, pubkey::Pubkey};
 use spl_token::state::Mint as SplMint;
-use spl_token_2022::state::Mint as SplMint2022;
+use spl_token_2022::{extension::StateWithExtensions, state::Mint as SplMint2022};
 use pyth_solana_receiver_sdk::price_update::PriceUpdateV2;
 use anyhow::anyhow;
 
-pub fn parse_mint_decimals(account: &Account) -> anyhow::Result<u8> {
+/// Mint fields needed to quote and swap, for both SPL Token and Token-2022 mints
+#[derive(Clone, Debug)]
+pub struct MintInfo {
+    pub decimals: u8,
+    pub token_program: Pubkey,
+}
+
+pub fn parse_mint_info(account: &Account) -> anyhow::Result<MintInfo> {
     if account.owner == spl_token::ID {
         let mint = SplMint::unpack(&account.data)
             .map_err(|e| anyhow!("SPL mint unpack failed: {:?}", e))?;
-        Ok(mint.decimals)
+        Ok(MintInfo {
+            decimals: mint.decimals,
+            token_program: spl_token::ID,
+        })
     } else if account.owner == spl_token_2022::ID {
-        let mint = SplMint2022::unpack(&account.data)
+        let mint = StateWithExtensions::<SplMint2022>::unpack(&account.data)
             .map_err(|e| anyhow!("Token2022 mint unpack failed: {:?}", e))?;
-        Ok(mint.decimals)
+        Ok(MintInfo {
+            decimals: mint.base.decimals,
+            token_program: spl_token_2022::ID,
+        })
     } else {
         Err(anyhow!("account is not a token mint"))
     }
 }
 
+pub fn parse_mint_decimals(account: &Account) -> anyhow::Result<u8> {
+    parse_mint_info(account).map(|mint_info| mint_info.decimals)
+}
+
 /// Parse a Pyth price account and return a u64 price scaled appropriately
 fn _parse_pyth_price(acc: &solana_sdk::account::Account) -> anyhow::Result<u64> {
     let price_data: &PriceUpdateV2 = &PriceUpdateV2::try_from_slice(acc.data.as_slice())
@@ -0,0 +1,23 @@
+// Each test binary only uses some of the offsets
+#![allow(dead_code)]
+
+// Offsets of the `Vault` fields in the vault account data
+pub const VAULT_IS_ACTIVE_OFFSET: usize = 16;
+pub const VAULT_BASE_FEE_OFFSET: usize = 17;
+pub const VAULT_TOKEN_MINT_OFFSET: usize = 25;
+pub const VAULT_INITIAL_LIQUIDITY_OFFSET: usize = 129;
+pub const VAULT_CURRENT_LIQUIDITY_OFFSET: usize = 137;
+pub const VAULT_MAX_LIQUIDITY_OFFSET: usize = 145;
+pub const VAULT_CUMULATIVE_YIELD_PER_LP_OFFSET: usize = 153;
+
+// Offsets of the `Treasury` fields in the treasury account data
+pub const TREASURY_STOPTAP_OFFSET: usize = 8;
+pub const TREASURY_FEE_BPS_OFFSET: usize = 41;
+
+// Offsets of `price_message.price` and `price_message.exponent` in a fully verified
+// `PriceUpdateV2` account
+pub const ORACLE_PRICE_OFFSET: usize = 73;
+pub const ORACLE_EXPONENT_OFFSET: usize = 89;
+
+// Offset of `amount` in an SPL token account
+pub const TOKEN_ACCOUNT_AMOUNT_OFFSET: usize = 64;
@@ -1,9 +1,12 @@
 use jupiter_amm_interface::{AccountMap, AmmContext, ClockRef, Swap, SwapMode};
 use jupiter_core::{
     amm::Amm,
-    amms::{oxedium_amm::OxediumAmm, test_harness::AmmTestHarness},
+    amms::{errors::OxediumError, oxedium_amm::OxediumAmm, test_harness::AmmTestHarness},
     route::route::get_token_mints_permutations,
-    test_harness::{load_test_programs, AmmTestAccountsSnapshot, AmmTestSwapParams, TestProgram},
+    test_harness::{
+        load_test_programs, refresh_pyth_price_updates, AmmTestAccountsSnapshot, AmmTestSwapParams,
+        TestProgram,
+    },
 };
 use solana_sdk::pubkey;
 use solana_sdk::pubkey::Pubkey;
@@ -16,8 +19,17 @@ struct TestAmmSettings {
     expect_swaps: Option<Vec<Swap>>,
     program_name: Option<String>,
     amounts: Option<Vec<u64>>,
+    /// Warp the snapshot clock, seen by both the amm and LiteSVM
+    unix_timestamp: Option<i64>,
+    before_test_setup: Option<fn(&dyn Amm, &mut AccountMap)>,
 }
 impl TestAmmSettings {
+    fn new_with_before_test_setup(before_test_setup: fn(&dyn Amm, &mut AccountMap)) -> Self {
+        Self {
+            before_test_setup: Some(before_test_setup),
+            ..Default::default()
+        }
+    }
     // fn new_with_tolerance(tolerance: u64) -> Self {
     //     Self {
     //         tolerance,
@@ -61,9 +73,16 @@ fn test_quoting_for_amm_key<T: Amm + 'static>(
         expect_swaps,
         program_name,
         amounts,
+        unix_timestamp,
+        before_test_setup: _,
     } = test_amm_settings;
 
-    let amm_test_accounts_snapshot = AmmTestAccountsSnapshot::load(amm_key, option.clone());
+    let mut amm_test_accounts_snapshot = AmmTestAccountsSnapshot::load(amm_key, option.clone());
+    if let Some(unix_timestamp) = unix_timestamp {
+        amm_test_accounts_snapshot
+            .warp_clock(unix_timestamp)
+            .unwrap();
+    }
     let keyed_account = amm_test_accounts_snapshot.get_keyed_account().unwrap();
     let amm_context = AmmContext {
         clock_ref: ClockRef::from(amm_test_accounts_snapshot.get_clock().unwrap()),
@@ -105,8 +124,9 @@ macro_rules! test_exact_in_amms {
                         "default" => None,
                         _ => Some($option.to_string()),
                     };
-                    let before_test_setup: Option<fn(&dyn Amm, &mut AccountMap)> = None;
-                    test_quoting_for_amm_key::<$amm_struct>($amm_key, SwapMode::ExactIn, false, $test_amm_settings.unwrap_or_default(), option, before_test_setup)
+                    let test_amm_settings: TestAmmSettings = $test_amm_settings.unwrap_or_default();
+                    let before_test_setup = test_amm_settings.before_test_setup;
+                    test_quoting_for_amm_key::<$amm_struct>($amm_key, SwapMode::ExactIn, false, test_amm_settings, option, before_test_setup)
                 }
                 #[tokio::test]
                 async fn [<test_quote_ $amm_key:lower _ $option:lower _ with_shared_accounts>] () {
@@ -114,46 +134,111 @@ macro_rules! test_exact_in_amms {
                         "default" => None,
                         _ => Some($option.to_string()),
                     };
-                    let before_test_setup: Option<fn(&dyn Amm, &mut AccountMap)> = None;
-                    test_quoting_for_amm_key::<$amm_struct>($amm_key, SwapMode::ExactIn, true, $test_amm_settings.unwrap_or_default(), option, before_test_setup)
+                    let test_amm_settings: TestAmmSettings = $test_amm_settings.unwrap_or_default();
+                    let before_test_setup = test_amm_settings.before_test_setup;
+                    test_quoting_for_amm_key::<$amm_struct>($amm_key, SwapMode::ExactIn, true, test_amm_settings, option, before_test_setup)
                 }
             }
         )*
     };
 }
 
-// macro_rules! test_exact_out_amms {
-//     ($(($amm_key:expr, $amm_struct:ty, $test_amm_settings:expr),)*) => {
-//         test_exact_out_amms!(
-//             $(($amm_key, $amm_struct, $test_amm_settings, "exact-out"),)*
-//         );
-//     };
-//     ($(($amm_key:expr, $amm_struct:ty, $test_amm_settings:expr, $option:expr),)*) => {
-//         $(
-//             paste::item! {
-//                 #[tokio::test]
-//                 async fn [<test_quote_ $amm_key:lower _ $option:lower>] () {
-//                     let option = Some($option.to_string());
-//                     let before_test_setup: Option<fn(&dyn Amm, &mut AccountMap)> = None;
-//                     test_quoting_for_amm_key::<$amm_struct>($amm_key, SwapMode::ExactOut, true, $test_amm_settings.unwrap_or_default(), option, before_test_setup)
-//                 }
-//                 #[tokio::test]
-//                 async fn [<test_quote_ $amm_key:lower _ $option:lower _ without_shared_accounts>] () {
-//                     let option = Some($option.to_string());
-//                     let before_test_setup: Option<fn(&dyn Amm, &mut AccountMap)> = None;
-//                     test_quoting_for_amm_key::<$amm_struct>($amm_key, SwapMode::ExactOut, false, $test_amm_settings.unwrap_or_default(), option, before_test_setup)
-//                 }
-//             }
-//         )*
-//     };
-// }
+macro_rules! test_exact_out_amms {
+    ($(($amm_key:expr, $amm_struct:ty, $test_amm_settings:expr),)*) => {
+        test_exact_out_amms!(
+            $(($amm_key, $amm_struct, $test_amm_settings, "exact-out"),)*
+        );
+    };
+    ($(($amm_key:expr, $amm_struct:ty, $test_amm_settings:expr, $option:expr),)*) => {
+        $(
+            paste::item! {
+                #[tokio::test]
+                async fn [<test_quote_ $amm_key:lower _ $option:lower>] () {
+                    let option = Some($option.to_string());
+                    let test_amm_settings: TestAmmSettings = $test_amm_settings.unwrap_or_default();
+                    let before_test_setup = test_amm_settings.before_test_setup;
+                    test_quoting_for_amm_key::<$amm_struct>($amm_key, SwapMode::ExactOut, true, test_amm_settings, option, before_test_setup)
+                }
+                #[tokio::test]
+                async fn [<test_quote_ $amm_key:lower _ $option:lower _ without_shared_accounts>] () {
+                    let option = Some($option.to_string());
+                    let test_amm_settings: TestAmmSettings = $test_amm_settings.unwrap_or_default();
+                    let before_test_setup = test_amm_settings.before_test_setup;
+                    test_quoting_for_amm_key::<$amm_struct>($amm_key, SwapMode::ExactOut, false, test_amm_settings, option, before_test_setup)
+                }
+            }
+        )*
+    };
+}
 
 const OXEDIUM_POOL: Pubkey = pubkey!("DZzt6k2QN77Khj4hYBZFyJVjzuV3KxkSqjMFaUvQoxz1");
 
+/// Clock of the Oxedium snapshots
+const OXEDIUM_SNAPSHOT_UNIX_TIMESTAMP: i64 = 1_768_505_994;
+/// `max_age_price` of the snapshotted Oxedium vaults
+const OXEDIUM_MAX_AGE_PRICE: i64 = 3;
+const SOL_ORACLE: Pubkey = pubkey!("7UVimffxr9ow1uXYxsr4LHAcV58mLzhmwaeKvJ1pjLiE");
+
+/// The snapshotted price updates are older than the vaults `max_age_price`,
+/// republish them at the snapshot clock
+fn refresh_oxedium_price_updates(_amm: &dyn Amm, account_map: &mut AccountMap) {
+    refresh_pyth_price_updates(account_map, OXEDIUM_SNAPSHOT_UNIX_TIMESTAMP);
+}
+
 // You can run a single test by doing: `cargo test test_quote_<lower_case_constant>_<default | option_name> -- --nocapture`
 
 test_exact_in_amms! {
-    (OXEDIUM_POOL, OxediumAmm, None),
+    (OXEDIUM_POOL, OxediumAmm, Some(TestAmmSettings::new_with_before_test_setup(refresh_oxedium_price_updates))),
+}
+
+test_exact_out_amms! {
+    (OXEDIUM_POOL, OxediumAmm, Some(TestAmmSettings::new_with_before_test_setup(refresh_oxedium_price_updates))),
+}
+
+#[tokio::test]
+async fn test_quote_oxedium_pool_oracle_at_max_age() {
+    let test_amm_settings = TestAmmSettings {
+        unix_timestamp: Some(OXEDIUM_SNAPSHOT_UNIX_TIMESTAMP + OXEDIUM_MAX_AGE_PRICE),
+        ..TestAmmSettings::new_with_before_test_setup(refresh_oxedium_price_updates)
+    };
+    let before_test_setup = test_amm_settings.before_test_setup;
+    test_quoting_for_amm_key::<OxediumAmm>(
+        OXEDIUM_POOL,
+        SwapMode::ExactIn,
+        false,
+        test_amm_settings,
+        None,
+        before_test_setup,
+    )
+}
+
+#[tokio::test]
+async fn test_quote_oxedium_pool_stale_oracle() {
+    let test_amm_settings = TestAmmSettings {
+        unix_timestamp: Some(OXEDIUM_SNAPSHOT_UNIX_TIMESTAMP + OXEDIUM_MAX_AGE_PRICE + 1),
+        restricted_mint_permutations: Some(vec![(
+            spl_token::native_mint::ID,
+            jupiter_core::constants::USDC_MINT,
+        )]),
+        expect_error: Some(
+            OxediumError::StaleOracle {
+                oracle: SOL_ORACLE,
+                age: OXEDIUM_MAX_AGE_PRICE + 1,
+                max_age: OXEDIUM_MAX_AGE_PRICE as u64,
+            }
+            .into(),
+        ),
+        ..TestAmmSettings::new_with_before_test_setup(refresh_oxedium_price_updates)
+    };
+    let before_test_setup = test_amm_settings.before_test_setup;
+    test_quoting_for_amm_key::<OxediumAmm>(
+        OXEDIUM_POOL,
+        SwapMode::ExactIn,
+        false,
+        test_amm_settings,
+        None,
+        before_test_setup,
+    )
 }
 
 #[allow(clippy::too_many_arguments)]
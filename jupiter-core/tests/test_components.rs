@@ -0,0 +1,571 @@
+use jupiter_core::{
+    amms::errors::OxediumError,
+    components::{
+        claimable_yield, compute_swap_math, compute_swap_math_exact_out, lp_token_value,
+        raw_amount_out, staker_position, CubicFeeCurve, FeeCurve, FeeCurveModel, LinearFeeCurve,
+        OraclePrice, PiecewiseFeeCurve, QuadraticFeeCurve, StakerPosition,
+    },
+    states::{Staker, Vault},
+    utils::SCALE,
+};
+use num_bigint::BigUint;
+use proptest::prelude::*;
+use pyth_solana_receiver_sdk::price_update::PriceFeedMessage;
+
+const SOL_PRICE: OraclePrice = OraclePrice {
+    price: 14_122_289_113,
+    exponent: -8,
+};
+const USDC_PRICE: OraclePrice = OraclePrice {
+    price: 99_976_890,
+    exponent: -8,
+};
+
+#[test]
+fn test_raw_amount_out_with_pyth_exponent() {
+    assert_eq!(
+        raw_amount_out(1_000_000_000, 9, 6, SOL_PRICE, USDC_PRICE).unwrap(),
+        141_255_535
+    );
+    assert_eq!(
+        raw_amount_out(1_110_000, 6, 9, USDC_PRICE, SOL_PRICE).unwrap(),
+        7_858_099
+    );
+}
+
+#[test]
+fn test_raw_amount_out_with_mixed_exponents() {
+    // Same SOL price expressed with 10 decimals instead of 8
+    let sol_price = OraclePrice::new(1_412_228_911_300, -10);
+    assert_eq!(
+        raw_amount_out(1_000_000_000, 9, 6, sol_price, USDC_PRICE).unwrap(),
+        raw_amount_out(1_000_000_000, 9, 6, SOL_PRICE, USDC_PRICE).unwrap(),
+    );
+
+    // 50 USD expressed with a positive, zero and negative exponent
+    let one_usd = OraclePrice::new(1, 0);
+    for price in [
+        OraclePrice::new(5, 1),
+        OraclePrice::new(50, 0),
+        OraclePrice::new(5_000_000_000, -8),
+    ] {
+        assert_eq!(
+            raw_amount_out(1_000_000, 6, 6, price, one_usd).unwrap(),
+            50_000_000
+        );
+    }
+
+    // 12345 tokens at 0.05 USD into a token worth 0.3 USD
+    assert_eq!(
+        raw_amount_out(
+            12_345,
+            0,
+            12,
+            OraclePrice::new(5, -2),
+            OraclePrice::new(3, -1)
+        )
+        .unwrap(),
+        2_057_500_000_000_000
+    );
+    assert_eq!(
+        raw_amount_out(1, 9, 6, OraclePrice::new(3, 2), OraclePrice::new(7, -3)).unwrap(),
+        42
+    );
+}
+
+#[test]
+fn test_raw_amount_out_exponent_overflow() {
+    assert!(raw_amount_out(1_000_000, 6, 6, OraclePrice::new(1, 39), USDC_PRICE).is_err());
+    assert!(raw_amount_out(1_000_000, 6, 6, SOL_PRICE, OraclePrice::new(1, -39)).is_err());
+}
+
+#[test]
+fn test_raw_amount_out_wide_intermediates() {
+    // amount_in * SCALE * price_in exceeds u128 while the result fits in u64
+    let price = OraclePrice::new(1_000_000_000_000_000_000, -18);
+    assert_eq!(
+        raw_amount_out(10_000_000_000_000_000_000, 9, 9, price, price).unwrap(),
+        10_000_000_000_000_000_000
+    );
+    assert_eq!(
+        raw_amount_out(
+            u64::MAX,
+            18,
+            18,
+            OraclePrice::new(u64::MAX, -8),
+            OraclePrice::new(u64::MAX, -8)
+        )
+        .unwrap(),
+        18_446_744_073_708_000_000
+    );
+    // Dust with a denominator beyond 256 bits
+    assert_eq!(raw_amount_out(1, 80, 0, SOL_PRICE, USDC_PRICE).unwrap(), 0);
+}
+
+#[test]
+fn test_raw_amount_out_rounds_like_the_program() {
+    // The program floors the fixed-point output before scaling it to the output decimals,
+    // a single floored fraction would give 411_522_333_333_333
+    assert_eq!(
+        raw_amount_out(
+            1_234_567,
+            6,
+            18,
+            OraclePrice::new(100_000_000, -8),
+            OraclePrice::new(300_000_000_000, -8)
+        )
+        .unwrap(),
+        411_522_333_000_000
+    );
+}
+
+#[test]
+fn test_raw_amount_out_matches_hand_computed_value() {
+    // 7 SOL at 141.22289113 USD into USDC at 0.99976890 USD, both without decimals:
+    // 7 * 14_122_289_113 / 99_976_890 = 988.788..., floored to 988
+    assert_eq!(raw_amount_out(7, 0, 0, SOL_PRICE, USDC_PRICE).unwrap(), 988);
+}
+
+#[test]
+fn test_raw_amount_out_rejects_zero_price() {
+    for (price_in, price_out) in [
+        (OraclePrice::new(0, -8), USDC_PRICE),
+        (SOL_PRICE, OraclePrice::new(0, -8)),
+    ] {
+        let error = raw_amount_out(1_000_000_000, 9, 6, price_in, price_out).unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<OxediumError>(),
+            Some(&OxediumError::NonPositiveOraclePrice { price: 0 })
+        );
+    }
+}
+
+/// Balanced vault charging `base_fee` bps on swaps out of it
+fn balanced_vault(base_fee: u64) -> Vault {
+    Vault {
+        is_active: true,
+        base_fee,
+        initial_liquidity: 1_000_000_000_000,
+        current_liquidity: 1_000_000_000_000,
+        max_liquidity: 10_000_000_000_000,
+        ..Default::default()
+    }
+}
+
+/// Net output of swapping `amount_in` between two balanced vaults at the same price
+fn net_amount_out(amount_in: u64, lp_fee_bps: u64, protocol_fee_bps: u64) -> Option<u64> {
+    let one_usd = OraclePrice::new(1, 0);
+    compute_swap_math(
+        amount_in,
+        one_usd,
+        one_usd,
+        6,
+        6,
+        &balanced_vault(lp_fee_bps),
+        &balanced_vault(lp_fee_bps),
+        protocol_fee_bps,
+        &QuadraticFeeCurve,
+    )
+    .ok()
+    .map(|result| result.net_amount_out)
+}
+
+fn exact_out_amount_in(net_amount_out: u64, lp_fee_bps: u64, protocol_fee_bps: u64) -> u64 {
+    let one_usd = OraclePrice::new(1, 0);
+    let (amount_in, result) = compute_swap_math_exact_out(
+        net_amount_out,
+        one_usd,
+        one_usd,
+        6,
+        6,
+        &balanced_vault(lp_fee_bps),
+        &balanced_vault(lp_fee_bps),
+        protocol_fee_bps,
+        &QuadraticFeeCurve,
+    )
+    .unwrap();
+    assert!(result.net_amount_out >= net_amount_out);
+    amount_in
+}
+
+#[test]
+fn test_exact_out_across_fee_steps() {
+    // Both fees step up at 2000, the net output drops by one unit
+    assert_eq!(net_amount_out(1_999, 30, 25), Some(1_990));
+    assert_eq!(net_amount_out(2_000, 30, 25), Some(1_989));
+    assert_eq!(net_amount_out(1_998, 30, 25), Some(1_989));
+    assert_eq!(exact_out_amount_in(1_990, 30, 25), 1_999);
+}
+
+#[test]
+fn test_swap_into_vault_without_initial_liquidity() {
+    // The program divides the LP fee by the output vault initial liquidity
+    let one_usd = OraclePrice::new(1, 0);
+    let vault_out = Vault {
+        initial_liquidity: 0,
+        ..balanced_vault(30)
+    };
+    let error = compute_swap_math(
+        1_000_000,
+        one_usd,
+        one_usd,
+        6,
+        6,
+        &balanced_vault(30),
+        &vault_out,
+        25,
+        &QuadraticFeeCurve,
+    )
+    .unwrap_err();
+    assert_eq!(
+        error.downcast_ref::<OxediumError>(),
+        Some(&OxediumError::NoInitialLiquidity {
+            mint: vault_out.token_mint
+        })
+    );
+}
+
+#[test]
+fn test_exact_out_is_minimal() {
+    for (lp_fee_bps, protocol_fee_bps) in [(30, 25), (5, 5), (300, 170), (4_999, 4_999)] {
+        // Smallest input netting each output amount, by brute force
+        let mut max_net_amount_out = 0;
+        for amount_in in 1..20_000 {
+            let Some(net) = net_amount_out(amount_in, lp_fee_bps, protocol_fee_bps) else {
+                continue;
+            };
+            for target in max_net_amount_out + 1..=net {
+                assert_eq!(
+                    exact_out_amount_in(target, lp_fee_bps, protocol_fee_bps),
+                    amount_in,
+                    "fees {lp_fee_bps} + {protocol_fee_bps} bps, target {target}"
+                );
+            }
+            max_net_amount_out = max_net_amount_out.max(net);
+        }
+    }
+}
+
+#[test]
+fn test_exact_out_with_large_price_ratio() {
+    // One input unit is worth 1e11 output units, so the raw output steps by 1e11 per input
+    // unit and no raw output between two steps can be reached
+    let (amount_in, result) = compute_swap_math_exact_out(
+        150_000_000_000,
+        OraclePrice::new(1, 0),
+        OraclePrice::new(1, -8),
+        6,
+        9,
+        &balanced_vault(30),
+        &balanced_vault(30),
+        25,
+        &QuadraticFeeCurve,
+    )
+    .unwrap();
+    assert_eq!(amount_in, 2);
+    assert_eq!(result.raw_amount_out, 200_000_000_000);
+    assert_eq!(result.net_amount_out, 198_900_000_000);
+}
+
+fn price_message(price: i64, exponent: i32) -> PriceFeedMessage {
+    PriceFeedMessage {
+        feed_id: [0; 32],
+        price,
+        conf: 0,
+        exponent,
+        publish_time: 0,
+        prev_publish_time: 0,
+        ema_price: price,
+        ema_conf: 0,
+    }
+}
+
+#[test]
+fn test_oracle_price_rejects_non_positive_prices() {
+    for price in [0, -1, i64::MIN] {
+        assert_eq!(
+            OraclePrice::from_price_message(&price_message(price, -8)),
+            None
+        );
+    }
+    assert_eq!(
+        OraclePrice::from_price_message(&price_message(i64::MAX, -8)),
+        Some(OraclePrice::new(i64::MAX as u64, -8))
+    );
+}
+
+#[test]
+fn test_fee_curves() {
+    for (deviation_bps, quadratic, linear, cubic) in [
+        (0, 0, 0, 0),
+        (1_000, 100, 1_000, 10),
+        (5_000, 2_500, 5_000, 1_250),
+        (10_000, 10_000, 10_000, 10_000),
+    ] {
+        assert_eq!(
+            QuadraticFeeCurve.curved_deviation_bps(deviation_bps),
+            quadratic
+        );
+        assert_eq!(LinearFeeCurve.curved_deviation_bps(deviation_bps), linear);
+        assert_eq!(CubicFeeCurve.curved_deviation_bps(deviation_bps), cubic);
+    }
+
+    let piecewise =
+        PiecewiseFeeCurve::new(vec![(1_000, 0), (5_000, 1_000), (10_000, 10_000)]).unwrap();
+    for (deviation_bps, curved_deviation_bps) in [
+        (0, 0),
+        (1_000, 0),
+        (3_000, 500),
+        (5_000, 1_000),
+        (7_500, 5_500),
+        (10_000, 10_000),
+    ] {
+        assert_eq!(
+            piecewise.curved_deviation_bps(deviation_bps),
+            curved_deviation_bps
+        );
+    }
+}
+
+#[test]
+fn test_piecewise_fee_curve_rejects_invalid_points() {
+    for points in [
+        vec![],
+        vec![(0, 0), (0, 100)],
+        vec![(5_000, 0), (1_000, 100)],
+        vec![(0, 1_000), (10_000, 500)],
+        vec![(0, 0), (20_000, 10_000)],
+        vec![(0, 0), (10_000, 20_000)],
+    ] {
+        assert_eq!(PiecewiseFeeCurve::new(points), None);
+    }
+}
+
+#[test]
+fn test_fee_curve_model_from_params() {
+    assert_eq!(FeeCurveModel::default(), FeeCurveModel::Quadratic);
+    assert_eq!(
+        serde_json::from_value::<FeeCurveModel>(serde_json::json!({ "type": "cubic" })).unwrap(),
+        FeeCurveModel::Cubic
+    );
+
+    let model: FeeCurveModel = serde_json::from_value(serde_json::json!({
+        "type": "piecewise",
+        "points": [[0, 0], [10_000, 10_000]],
+    }))
+    .unwrap();
+    assert_eq!(model.curved_deviation_bps(4_321), 4_321);
+
+    assert!(serde_json::from_value::<FeeCurveModel>(serde_json::json!({
+        "type": "piecewise",
+        "points": [[10_000, 0], [0, 10_000]],
+    }))
+    .is_err());
+}
+
+#[test]
+fn test_claimable_yield() {
+    let vault = Vault {
+        cumulative_yield_per_lp: 3 * SCALE / 2,
+        ..Default::default()
+    };
+    let staker = Staker {
+        last_cumulative_yield: SCALE,
+        pending_claim: 7,
+        ..Default::default()
+    };
+    // 0.5 of yield per LP token accrued since the checkpoint
+    assert_eq!(
+        claimable_yield(&vault, &staker, 1_000_001).unwrap(),
+        500_007
+    );
+    assert_eq!(claimable_yield(&vault, &staker, 0).unwrap(), 7);
+
+    // A checkpoint ahead of the vault is corrupted state
+    let staker = Staker {
+        last_cumulative_yield: 2 * SCALE,
+        ..Default::default()
+    };
+    let error = claimable_yield(&vault, &staker, 1).unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<OxediumError>(),
+        Some(OxediumError::MathOverflow(_))
+    ));
+}
+
+#[test]
+fn test_lp_token_value() {
+    let vault = Vault {
+        initial_liquidity: 2_000_000,
+        current_liquidity: 3_000_000,
+        ..Default::default()
+    };
+    // LP tokens are redeemed 1:1 down to half of the initial liquidity
+    assert_eq!(lp_token_value(&vault, 1_000).unwrap(), 1_000);
+    assert_eq!(lp_token_value(&vault, 0).unwrap(), 0);
+    let half_drained_vault = Vault {
+        current_liquidity: 1_000_000,
+        ..vault
+    };
+    assert_eq!(lp_token_value(&half_drained_vault, 1_000).unwrap(), 1_000);
+
+    // Below it, only 2% of the LP amount is paid out, at least 1 token
+    let drained_vault = Vault {
+        current_liquidity: 999_999,
+        ..vault
+    };
+    assert_eq!(lp_token_value(&drained_vault, 10_001).unwrap(), 200);
+    assert_eq!(lp_token_value(&drained_vault, 10).unwrap(), 1);
+    assert_eq!(lp_token_value(&drained_vault, 1).unwrap(), 1);
+    assert_eq!(lp_token_value(&drained_vault, 0).unwrap(), 0);
+
+    let empty_vault = Vault::default();
+    assert_eq!(
+        lp_token_value(&empty_vault, 1_000)
+            .unwrap_err()
+            .downcast_ref::<OxediumError>(),
+        Some(&OxediumError::MathOverflow("vault liquidity ratio"))
+    );
+
+    let staker = Staker {
+        pending_claim: 42,
+        ..Default::default()
+    };
+    assert_eq!(
+        staker_position(&drained_vault, &staker, 1_000).unwrap(),
+        StakerPosition {
+            lp_balance: 1_000,
+            lp_value: 20,
+            claimable_yield: 42,
+        }
+    );
+}
+
+/// The rounding steps of the program on arbitrary precision integers, to check the 256-bit
+/// arithmetic rather than the rounding itself
+fn reference_amount_out(
+    amount_in: u64,
+    decimals_in: u32,
+    decimals_out: u32,
+    price_in: OraclePrice,
+    price_out: OraclePrice,
+) -> BigUint {
+    let pow10 = |exponent: u32| BigUint::from(10u8).pow(exponent);
+    let amount_fp = BigUint::from(amount_in) * SCALE / pow10(decimals_in);
+    let usd_fp = amount_fp * price_in.price;
+    let usd_fp = if price_in.exponent < 0 {
+        usd_fp / pow10(price_in.exponent.unsigned_abs())
+    } else {
+        usd_fp * pow10(price_in.exponent.unsigned_abs())
+    };
+    let out_fp = if price_out.exponent < 0 {
+        usd_fp * pow10(price_out.exponent.unsigned_abs()) / price_out.price
+    } else {
+        usd_fp / (BigUint::from(price_out.price) * pow10(price_out.exponent.unsigned_abs()))
+    };
+    out_fp * pow10(decimals_out) / SCALE
+}
+
+proptest! {
+    #[test]
+    fn test_oracle_price_is_positive(price in any::<i64>(), exponent in -20i32..20) {
+        match OraclePrice::from_price_message(&price_message(price, exponent)) {
+            Some(oracle_price) => {
+                prop_assert!(price > 0);
+                prop_assert_eq!(oracle_price, OraclePrice::new(price as u64, exponent));
+            }
+            None => prop_assert!(price <= 0),
+        }
+    }
+
+    #[test]
+    fn test_raw_amount_out_handles_extreme_prices(
+        amount_in in any::<u64>(),
+        decimals_in in 0u32..20,
+        decimals_out in 0u32..20,
+        price_in in prop_oneof![Just(0u64), Just(1u64), Just(u64::MAX), any::<u64>()],
+        price_out in prop_oneof![Just(0u64), Just(1u64), Just(u64::MAX), any::<u64>()],
+        exponent_in in -40i32..40,
+        exponent_out in -40i32..40,
+    ) {
+        // Extreme inputs must surface as errors, never as panics or divisions by zero
+        let result = raw_amount_out(
+            amount_in,
+            decimals_in,
+            decimals_out,
+            OraclePrice::new(price_in, exponent_in),
+            OraclePrice::new(price_out, exponent_out),
+        );
+        if price_in == 0 || price_out == 0 {
+            prop_assert!(result.is_err());
+        }
+    }
+
+    #[test]
+    fn test_raw_amount_out_matches_big_integer_reference(
+        amount_in in prop_oneof![Just(1u64), Just(u64::MAX), any::<u64>()],
+        decimals_in in 0u32..20,
+        decimals_out in 0u32..20,
+        price_in in prop_oneof![Just(1u64), Just(u64::MAX), 1..=u64::MAX],
+        price_out in prop_oneof![Just(1u64), Just(u64::MAX), 1..=u64::MAX],
+        // Above 8, a price exponent can overflow 256 bits where the result still fits in u64,
+        // Pyth publishes negative exponents
+        exponent_in in -40i32..=8,
+        exponent_out in -40i32..40,
+    ) {
+        let price_in = OraclePrice::new(price_in, exponent_in);
+        let price_out = OraclePrice::new(price_out, exponent_out);
+        let expected = reference_amount_out(amount_in, decimals_in, decimals_out, price_in, price_out);
+        let result = raw_amount_out(amount_in, decimals_in, decimals_out, price_in, price_out);
+        match u64::try_from(&expected) {
+            Ok(expected) => prop_assert_eq!(result.unwrap(), expected),
+            Err(_) => prop_assert!(result.is_err()),
+        }
+    }
+
+    #[test]
+    fn test_raw_amount_out_never_exceeds_exact_value(
+        amount_in in any::<u64>(),
+        decimals_in in 0u32..20,
+        decimals_out in 0u32..20,
+        price_in in 1..=u64::MAX,
+        price_out in 1..=u64::MAX,
+        exponent_in in -20i32..=8,
+        exponent_out in -20i32..20,
+    ) {
+        // Rounding only ever drops value: the output is at most the exact rational
+        // amount_in * price_in * 10^exponent_in * 10^decimals_out
+        //     / (10^decimals_in * price_out * 10^exponent_out)
+        let Ok(result) = raw_amount_out(
+            amount_in,
+            decimals_in,
+            decimals_out,
+            OraclePrice::new(price_in, exponent_in),
+            OraclePrice::new(price_out, exponent_out),
+        ) else {
+            return Ok(());
+        };
+        let pow10 = |exponent: i32| BigUint::from(10u8).pow(exponent.unsigned_abs());
+        let mut numerator = BigUint::from(amount_in) * price_in * pow10(decimals_out as i32);
+        let mut denominator = pow10(decimals_in as i32) * price_out;
+        for (exponent, sign) in [(exponent_in, 1), (exponent_out, -1)] {
+            if exponent * sign >= 0 {
+                numerator *= pow10(exponent);
+            } else {
+                denominator *= pow10(exponent);
+            }
+        }
+        prop_assert!(BigUint::from(result) * denominator <= numerator);
+    }
+
+    #[test]
+    fn test_fee_curves_are_bounded_and_monotonic(deviation_bps in 0u64..10_000) {
+        let piecewise = PiecewiseFeeCurve::new(vec![(1_000, 0), (5_000, 1_000), (10_000, 10_000)]).unwrap();
+        let curves: [&dyn FeeCurve; 4] = [&QuadraticFeeCurve, &LinearFeeCurve, &CubicFeeCurve, &piecewise];
+        for curve in curves {
+            let curved_deviation_bps = curve.curved_deviation_bps(deviation_bps);
+            prop_assert!(curved_deviation_bps <= 10_000);
+            prop_assert!(curved_deviation_bps <= curve.curved_deviation_bps(deviation_bps + 1));
+        }
+    }
+}
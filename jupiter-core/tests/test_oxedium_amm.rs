@@ -0,0 +1,1175 @@
+mod common;
+
+use borsh::BorshDeserialize;
+use common::{
+    ORACLE_PRICE_OFFSET, TREASURY_FEE_BPS_OFFSET, TREASURY_STOPTAP_OFFSET,
+    VAULT_CUMULATIVE_YIELD_PER_LP_OFFSET, VAULT_CURRENT_LIQUIDITY_OFFSET, VAULT_IS_ACTIVE_OFFSET,
+    VAULT_MAX_LIQUIDITY_OFFSET, VAULT_TOKEN_MINT_OFFSET,
+};
+use jupiter_amm_interface::{ClockRef, SwapAndAccountMetas, SwapParams};
+use jupiter_core::{
+    amm::{AccountMap, Amm, AmmContext, Quote, QuoteParams, SwapMode},
+    amms::{
+        amm_program_id_to_labels::{program_id_to_label, unregister_program_id},
+        components::{OraclePrice, StakerPosition},
+        errors::OxediumError,
+        loader::amm_factory,
+        oxedium_amm::{
+            find_staker_pda, find_treasury_pda, find_vault_pda, OxediumAmm, QuoteMode, UpdateMode,
+            OXEDIUM_PROGRAM_ID,
+        },
+    },
+    constants::USDC_MINT,
+    states::{Staker, Vault},
+    test_harness::{
+        load_accounts_snapshot, load_test_programs, refresh_pyth_price_updates,
+        snapshot_directory_name, AmmTestAccountsSnapshot, AmmTestHarness,
+        TOKEN2022_MINT_TO_IN_AMOUNT,
+    },
+    utils::{SCALE, STAKER_DISCRIMINATOR, VAULT_DISCRIMINATOR},
+};
+use pyth_solana_receiver_sdk::price_update::VerificationLevel;
+use rust_decimal::Decimal;
+use serde_json::{json, Value};
+use solana_sdk::account::Account;
+use solana_sdk::pubkey;
+use solana_sdk::pubkey::Pubkey;
+use spl_associated_token_account::get_associated_token_address_with_program_id;
+use spl_token_2022::{
+    extension::{
+        transfer_fee::{TransferFee, TransferFeeConfig},
+        BaseStateWithExtensionsMut, ExtensionType, StateWithExtensionsMut,
+    },
+    state::Mint,
+};
+use std::collections::HashSet;
+use std::sync::atomic::Ordering;
+
+const OXEDIUM_POOL: Pubkey = pubkey!("DZzt6k2QN77Khj4hYBZFyJVjzuV3KxkSqjMFaUvQoxz1");
+const JUPITER_PROGRAM_ID: Pubkey = pubkey!("JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4");
+const SOL_MINT: Pubkey = spl_token::native_mint::ID;
+const SOL_ORACLE: Pubkey = pubkey!("7UVimffxr9ow1uXYxsr4LHAcV58mLzhmwaeKvJ1pjLiE");
+const USDC_ORACLE: Pubkey = pubkey!("Dpw1EAVrSB1ibxiDQyTAW6Zip3J4Btk2x4SgApQCeFbX");
+const SOL_VAULT: Pubkey = pubkey!("6unq3WF6nRLpAqfYUAcLtXKr2tQnpAmoEHt7QGHBSkHC");
+const USDC_VAULT: Pubkey = pubkey!("DQc8SkUkRvp4bAyyCcLtX5Pgwb9gzioMzEVpzPxeZHbN");
+
+/// Loads the Oxedium snapshot with its price updates republished at the snapshot clock
+fn load_oxedium_amm(params: Option<Value>) -> OxediumAmm {
+    load_oxedium_amm_with_accounts(params, |_| {})
+}
+
+fn load_oxedium_amm_with_accounts(
+    params: Option<Value>,
+    modify_accounts: impl FnOnce(&mut AccountMap),
+) -> OxediumAmm {
+    let amm_test_accounts_snapshot = AmmTestAccountsSnapshot::load(OXEDIUM_POOL, None);
+    let clock = amm_test_accounts_snapshot.get_clock().unwrap();
+    let mut keyed_account = amm_test_accounts_snapshot.get_keyed_account().unwrap();
+    keyed_account.params = params;
+    let amm_context = AmmContext {
+        clock_ref: ClockRef::from(clock.clone()),
+    };
+    let mut amm = OxediumAmm::from_keyed_account(&keyed_account, &amm_context).unwrap();
+
+    let mut account_map = load_accounts_snapshot(&snapshot_directory_name(OXEDIUM_POOL, None));
+    refresh_pyth_price_updates(&mut account_map, clock.unix_timestamp);
+    modify_accounts(&mut account_map);
+    // Vaults are loaded first, then the mints and oracles they reference
+    for _ in 0..2 {
+        amm.update(&account_map).unwrap();
+    }
+    amm
+}
+
+fn quote(
+    amm: &OxediumAmm,
+    input_mint: Pubkey,
+    output_mint: Pubkey,
+    amount: u64,
+) -> anyhow::Result<Quote> {
+    amm.quote(&QuoteParams {
+        amount,
+        input_mint,
+        output_mint,
+        swap_mode: SwapMode::ExactIn,
+    })
+}
+
+fn swap_and_account_metas(
+    amm: &OxediumAmm,
+    source_mint: Pubkey,
+    destination_mint: Pubkey,
+) -> anyhow::Result<SwapAndAccountMetas> {
+    amm.get_swap_and_account_metas(&SwapParams {
+        swap_mode: SwapMode::ExactIn,
+        source_mint,
+        destination_mint,
+        source_token_account: Pubkey::new_unique(),
+        destination_token_account: Pubkey::new_unique(),
+        token_transfer_authority: Pubkey::new_unique(),
+        quote_mint_to_referrer: None,
+        in_amount: 1_000_000,
+        out_amount: 1_000_000,
+        jupiter_program_id: &JUPITER_PROGRAM_ID,
+        missing_dynamic_accounts_as_default: false,
+    })
+}
+
+#[test]
+fn test_swap_account_metas_follow_idl() {
+    let amm = load_oxedium_amm(None);
+    let SwapAndAccountMetas { account_metas, .. } =
+        swap_and_account_metas(&amm, SOL_MINT, USDC_MINT).unwrap();
+    assert_eq!(account_metas.len(), amm.get_accounts_len());
+
+    let writable_accounts = account_metas
+        .iter()
+        .map(|account_meta| account_meta.is_writable)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        writable_accounts,
+        [
+            true, false, false, false, false, true, true, true, true, true, true, true, false,
+            false, false
+        ]
+    );
+    assert!(account_metas
+        .iter()
+        .all(|account_meta| !account_meta.is_signer));
+    assert_eq!(account_metas[1].pubkey, SOL_MINT);
+    assert_eq!(account_metas[2].pubkey, USDC_MINT);
+    assert_eq!(account_metas[7].pubkey, SOL_VAULT);
+    assert_eq!(account_metas[8].pubkey, USDC_VAULT);
+    assert_eq!(account_metas[9].pubkey, OXEDIUM_POOL);
+    assert_eq!(account_metas[13].pubkey, spl_token::ID);
+}
+
+#[test]
+fn test_cached_pdas_match_derived_pdas() {
+    let amm = load_oxedium_amm(None);
+    assert_eq!(amm.treasury_pda, find_treasury_pda(&amm.program_id));
+    assert_eq!(amm.treasury_pda, OXEDIUM_POOL);
+    for (mint, vault) in [(SOL_MINT, SOL_VAULT), (USDC_MINT, USDC_VAULT)] {
+        assert_eq!(
+            amm.vault_pdas[&mint],
+            find_vault_pda(&mint, &amm.program_id)
+        );
+        assert_eq!(amm.vault_pdas[&mint], vault);
+        assert_eq!(
+            amm.treasury_atas[&mint],
+            get_associated_token_address_with_program_id(&amm.treasury_pda, &mint, &spl_token::ID)
+        );
+    }
+}
+
+#[test]
+fn test_underlying_liquidities_are_stable_across_updates() {
+    let mut amm = load_oxedium_amm(None);
+    let expected_underlying_liquidities = HashSet::from([SOL_VAULT, USDC_VAULT]);
+    assert_eq!(
+        amm.underlying_liquidities(),
+        Some(expected_underlying_liquidities)
+    );
+
+    let underlying_liquidities = amm.underlying_liquidities();
+    let mut account_map = load_accounts_snapshot(&snapshot_directory_name(OXEDIUM_POOL, None));
+    refresh_pyth_price_updates(
+        &mut account_map,
+        amm.clock_ref.unix_timestamp.load(Ordering::Relaxed),
+    );
+    for _ in 0..3 {
+        amm.update(&account_map).unwrap();
+        assert_eq!(amm.underlying_liquidities(), underlying_liquidities);
+    }
+    assert_eq!(
+        amm.clone_amm().underlying_liquidities(),
+        underlying_liquidities
+    );
+
+    // A configured vault that does not exist is never loaded
+    let amm = load_oxedium_amm(Some(json!({
+        "vaults": [SOL_VAULT.to_string(), USDC_VAULT.to_string(), Pubkey::new_unique().to_string()]
+    })));
+    assert_eq!(amm.underlying_liquidities(), underlying_liquidities);
+}
+
+#[test]
+fn test_conservative_quote_is_below_default_quote() {
+    let default_amm = load_oxedium_amm(None);
+    let conservative_amm = load_oxedium_amm(Some(json!({ "quoteMode": "conservative" })));
+    assert_eq!(conservative_amm.quote_mode, QuoteMode::Conservative);
+
+    for (input_mint, output_mint, amount) in [
+        (SOL_MINT, USDC_MINT, 25_000_000),
+        (USDC_MINT, SOL_MINT, 1_110_000),
+    ] {
+        let default_quote = quote(&default_amm, input_mint, output_mint, amount).unwrap();
+        let conservative_quote = quote(&conservative_amm, input_mint, output_mint, amount).unwrap();
+        assert!(conservative_quote.out_amount < default_quote.out_amount);
+    }
+}
+
+#[test]
+fn test_quote_rejects_wide_confidence() {
+    // The snapshotted SOL confidence is 3 bps of the price, USDC is 6 bps
+    let amm = load_oxedium_amm(Some(json!({ "maxConfidenceBps": 5 })));
+    let error = quote(&amm, SOL_MINT, USDC_MINT, 25_000_000).unwrap_err();
+    assert_eq!(
+        error.downcast_ref::<OxediumError>(),
+        Some(&OxediumError::OracleConfidenceTooWide {
+            oracle: USDC_ORACLE,
+            confidence_bps: 6,
+            max_confidence_bps: 5,
+        })
+    );
+
+    let amm = load_oxedium_amm(Some(json!({ "maxConfidenceBps": 6 })));
+    assert!(quote(&amm, SOL_MINT, USDC_MINT, 25_000_000).is_ok());
+}
+
+#[test]
+fn test_treasury_is_loaded() {
+    let amm = load_oxedium_amm(None);
+    assert!(!amm.treasury.stoptap);
+    assert_eq!(amm.treasury.fee_bps, 0);
+    assert!(amm.is_active());
+
+    // The treasury is the keyed account
+    let amm = load_oxedium_amm_with_accounts(None, |account_map| {
+        account_map.get_mut(&OXEDIUM_POOL).unwrap().data[TREASURY_STOPTAP_OFFSET] = 1;
+    });
+    assert!(amm.treasury.stoptap);
+    assert!(!amm.is_active());
+}
+
+#[test]
+fn test_quote_charges_treasury_protocol_fee() {
+    let fee_bps = 25u64;
+    let amm = load_oxedium_amm_with_accounts(None, |account_map| {
+        let data = &mut account_map.get_mut(&OXEDIUM_POOL).unwrap().data;
+        data[TREASURY_FEE_BPS_OFFSET..TREASURY_FEE_BPS_OFFSET + 8]
+            .copy_from_slice(&fee_bps.to_le_bytes());
+    });
+    assert_eq!(amm.treasury.fee_bps, fee_bps);
+
+    let without_protocol_fee =
+        quote(&load_oxedium_amm(None), SOL_MINT, USDC_MINT, 25_000_000).unwrap();
+    let with_protocol_fee = quote(&amm, SOL_MINT, USDC_MINT, 25_000_000).unwrap();
+    assert!(with_protocol_fee.fee_amount > without_protocol_fee.fee_amount);
+    assert_eq!(
+        without_protocol_fee.out_amount - with_protocol_fee.out_amount,
+        with_protocol_fee.fee_amount - without_protocol_fee.fee_amount
+    );
+}
+
+#[test]
+fn test_quote_rejects_inactive_vault() {
+    let amm = load_oxedium_amm_with_accounts(None, |account_map| {
+        account_map.get_mut(&USDC_VAULT).unwrap().data[VAULT_IS_ACTIVE_OFFSET] = 0;
+    });
+    assert_eq!(amm.get_reserve_mints(), vec![SOL_MINT]);
+
+    for (input_mint, output_mint, amount) in [
+        (SOL_MINT, USDC_MINT, 25_000_000),
+        (USDC_MINT, SOL_MINT, 1_110_000),
+    ] {
+        let error = quote(&amm, input_mint, output_mint, amount).unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<OxediumError>(),
+            Some(&OxediumError::VaultInactive { mint: USDC_MINT })
+        );
+    }
+}
+
+#[test]
+fn test_quote_rejects_input_above_vault_capacity() {
+    let current_liquidity = 20_000_000_000u64;
+    let max_liquidity = current_liquidity + 10_000_000;
+    let amm = load_oxedium_amm_with_accounts(None, |account_map| {
+        let data = &mut account_map.get_mut(&SOL_VAULT).unwrap().data;
+        data[VAULT_MAX_LIQUIDITY_OFFSET..VAULT_MAX_LIQUIDITY_OFFSET + 8]
+            .copy_from_slice(&max_liquidity.to_le_bytes());
+    });
+
+    assert!(quote(&amm, SOL_MINT, USDC_MINT, 10_000_000).is_ok());
+    let error = quote(&amm, SOL_MINT, USDC_MINT, 25_000_000).unwrap_err();
+    assert_eq!(
+        error.downcast_ref::<OxediumError>(),
+        Some(&OxediumError::VaultCapacityExceeded {
+            mint: SOL_MINT,
+            current_liquidity,
+            amount_in: 25_000_000,
+            max_liquidity,
+        })
+    );
+
+    // The output side is not capacity bound
+    assert!(quote(&amm, USDC_MINT, SOL_MINT, 1_110_000).is_ok());
+}
+
+/// Snapshot accounts with the SOL oracle account truncated
+fn account_map_with_truncated_sol_oracle(amm: &OxediumAmm) -> AccountMap {
+    let mut account_map = load_accounts_snapshot(&snapshot_directory_name(OXEDIUM_POOL, None));
+    refresh_pyth_price_updates(
+        &mut account_map,
+        amm.clock_ref.unix_timestamp.load(Ordering::Relaxed),
+    );
+    account_map.get_mut(&SOL_ORACLE).unwrap().data.truncate(4);
+    account_map
+}
+
+#[test]
+fn test_strict_update_rejects_malformed_account() {
+    let mut amm = load_oxedium_amm(Some(json!({ "updateMode": "strict" })));
+    assert_eq!(amm.update_mode, UpdateMode::Strict);
+    let account_map = account_map_with_truncated_sol_oracle(&amm);
+
+    let error = amm.update(&account_map).unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<OxediumError>(),
+        Some(OxediumError::InvalidAccount { address, .. }) if *address == SOL_ORACLE
+    ));
+    assert!(amm.stale_accounts.is_empty());
+    assert!(quote(&amm, SOL_MINT, USDC_MINT, 25_000_000).is_ok());
+}
+
+#[test]
+fn test_lenient_update_keeps_last_good_state() {
+    let mut amm = load_oxedium_amm(None);
+    assert_eq!(amm.update_mode, UpdateMode::Lenient);
+    let expected_quote = quote(&amm, SOL_MINT, USDC_MINT, 25_000_000).unwrap();
+    let account_map = account_map_with_truncated_sol_oracle(&amm);
+
+    amm.update(&account_map).unwrap();
+    assert!(amm.stale_accounts.contains(&SOL_ORACLE));
+    assert_eq!(amm.stale_accounts.len(), 1);
+    let quote_after_update = quote(&amm, SOL_MINT, USDC_MINT, 25_000_000).unwrap();
+    assert_eq!(quote_after_update.out_amount, expected_quote.out_amount);
+
+    // The account is no longer stale once it decodes again
+    let mut account_map = load_accounts_snapshot(&snapshot_directory_name(OXEDIUM_POOL, None));
+    refresh_pyth_price_updates(
+        &mut account_map,
+        amm.clock_ref.unix_timestamp.load(Ordering::Relaxed),
+    );
+    amm.update(&account_map).unwrap();
+    assert!(amm.stale_accounts.is_empty());
+}
+
+fn quote_params(input_mint: Pubkey, output_mint: Pubkey, amount: u64) -> QuoteParams {
+    QuoteParams {
+        amount,
+        input_mint,
+        output_mint,
+        swap_mode: SwapMode::ExactIn,
+    }
+}
+
+#[test]
+fn test_quote_detailed_breaks_down_quote() {
+    let amm = load_oxedium_amm(None);
+    let params = quote_params(SOL_MINT, USDC_MINT, 25_000_000);
+    let details = amm.quote_detailed(&params).unwrap();
+    let quote = amm.quote(&params).unwrap();
+
+    assert_eq!(details.in_amount, quote.in_amount);
+    assert_eq!(details.out_amount, quote.out_amount);
+    assert_eq!(
+        details.lp_fee_amount + details.protocol_fee_amount,
+        quote.fee_amount
+    );
+    assert_eq!(
+        details.raw_amount_out - details.lp_fee_amount - details.protocol_fee_amount,
+        details.out_amount
+    );
+    assert_eq!(details.price_in, OraclePrice::new(14_122_289_113, -8));
+    assert_eq!(details.price_out, OraclePrice::new(99_976_890, -8));
+    assert_eq!(details.protocol_fee_bps, amm.treasury.fee_bps);
+    assert_eq!(details.deviation_bps, 0);
+
+    // Draining the output vault by 10% raises the swap fee along the fee curve
+    let drained_amm = load_oxedium_amm_with_accounts(None, |account_map| {
+        let data = &mut account_map.get_mut(&USDC_VAULT).unwrap().data;
+        data[VAULT_CURRENT_LIQUIDITY_OFFSET..VAULT_CURRENT_LIQUIDITY_OFFSET + 8]
+            .copy_from_slice(&2_435_615_714u64.to_le_bytes());
+    });
+    let drained_details = drained_amm.quote_detailed(&params).unwrap();
+    assert_eq!(drained_details.deviation_bps, 1_000);
+    assert!(drained_details.swap_fee_bps > details.swap_fee_bps);
+    assert_eq!(drained_details.raw_amount_out, details.raw_amount_out);
+    assert!(drained_details.lp_fee_amount > details.lp_fee_amount);
+}
+
+#[test]
+fn test_fee_pct_is_relative_to_raw_amount_out() {
+    let amm = load_oxedium_amm_with_accounts(None, |account_map| {
+        let data = &mut account_map.get_mut(&OXEDIUM_POOL).unwrap().data;
+        data[TREASURY_FEE_BPS_OFFSET..TREASURY_FEE_BPS_OFFSET + 8]
+            .copy_from_slice(&25u64.to_le_bytes());
+    });
+
+    // SOL has 9 decimals and USDC 6, so the input and output units differ in both directions
+    for (input_mint, output_mint, amount) in [
+        (SOL_MINT, USDC_MINT, 25_000_000),
+        (USDC_MINT, SOL_MINT, 1_110_000),
+        (SOL_MINT, USDC_MINT, 1_000_000_000),
+        (USDC_MINT, SOL_MINT, 100_000_000),
+    ] {
+        let params = quote_params(input_mint, output_mint, amount);
+        let details = amm.quote_detailed(&params).unwrap();
+        let quote = amm.quote(&params).unwrap();
+
+        assert_eq!(quote.fee_mint, output_mint);
+        assert_eq!(
+            quote.fee_pct,
+            Decimal::from(quote.fee_amount) / Decimal::from(details.raw_amount_out)
+        );
+        // Each fee is floored on the raw output, so the percentage is within
+        // one output unit per fee of the fee rate
+        let fee_rate =
+            Decimal::from(details.swap_fee_bps + details.protocol_fee_bps) / Decimal::from(10_000);
+        let tolerance = Decimal::from(2) / Decimal::from(details.raw_amount_out);
+        assert!(
+            (quote.fee_pct - fee_rate).abs() <= tolerance,
+            "fee_pct {} is not close to the fee rate {fee_rate}",
+            quote.fee_pct
+        );
+    }
+}
+
+#[test]
+fn test_max_tradable_amount_is_bound_by_output_liquidity() {
+    let amm = load_oxedium_amm(None);
+
+    for (input_mint, output_mint) in [(SOL_MINT, USDC_MINT), (USDC_MINT, SOL_MINT)] {
+        let details = amm.max_tradable_amount(input_mint, output_mint).unwrap();
+        let vault_out = &amm.vaults[&output_mint];
+        assert!(
+            details.out_amount + details.lp_fee_amount + details.protocol_fee_amount
+                <= vault_out.current_liquidity
+        );
+        assert_eq!(
+            amm.quote_detailed(&quote_params(input_mint, output_mint, details.in_amount))
+                .unwrap(),
+            details
+        );
+
+        let error = quote(&amm, input_mint, output_mint, details.in_amount + 1).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<OxediumError>(),
+            Some(OxediumError::InsufficientLiquidity { mint, .. }) if *mint == output_mint
+        ));
+    }
+}
+
+#[test]
+fn test_max_tradable_amount_is_bound_by_input_capacity() {
+    let max_liquidity = 20_000_000_000u64 + 10_000_000;
+    let amm = load_oxedium_amm_with_accounts(None, |account_map| {
+        let data = &mut account_map.get_mut(&SOL_VAULT).unwrap().data;
+        data[VAULT_MAX_LIQUIDITY_OFFSET..VAULT_MAX_LIQUIDITY_OFFSET + 8]
+            .copy_from_slice(&max_liquidity.to_le_bytes());
+    });
+
+    let details = amm.max_tradable_amount(SOL_MINT, USDC_MINT).unwrap();
+    assert_eq!(details.in_amount, 10_000_000);
+
+    // An inactive vault cannot be traded at all
+    let amm = load_oxedium_amm_with_accounts(None, |account_map| {
+        account_map.get_mut(&USDC_VAULT).unwrap().data[VAULT_IS_ACTIVE_OFFSET] = 0;
+    });
+    let error = amm.max_tradable_amount(SOL_MINT, USDC_MINT).unwrap_err();
+    assert_eq!(
+        error.downcast_ref::<OxediumError>(),
+        Some(&OxediumError::VaultInactive { mint: USDC_MINT })
+    );
+}
+
+#[test]
+fn test_max_tradable_amount_clears_dust_inputs() {
+    // At 999 USD per SOL one USDC unit buys one lamport, both fees then take a unit each
+    let amm = load_oxedium_amm_with_accounts(None, |account_map| {
+        let data = &mut account_map.get_mut(&SOL_ORACLE).unwrap().data;
+        data[ORACLE_PRICE_OFFSET..ORACLE_PRICE_OFFSET + 8]
+            .copy_from_slice(&99_900_000_000i64.to_le_bytes());
+        let data = &mut account_map.get_mut(&OXEDIUM_POOL).unwrap().data;
+        data[TREASURY_FEE_BPS_OFFSET..TREASURY_FEE_BPS_OFFSET + 8]
+            .copy_from_slice(&25u64.to_le_bytes());
+    });
+    let error = quote(&amm, USDC_MINT, SOL_MINT, 1).unwrap_err();
+    assert_eq!(
+        error.downcast_ref::<OxediumError>(),
+        Some(&OxediumError::FeeOverflow)
+    );
+    assert!(quote(&amm, USDC_MINT, SOL_MINT, 2).is_ok());
+
+    let details = amm.max_tradable_amount(USDC_MINT, SOL_MINT).unwrap();
+    assert!(
+        details.out_amount + details.lp_fee_amount + details.protocol_fee_amount
+            <= amm.vaults[&SOL_MINT].current_liquidity
+    );
+    let error = quote(&amm, USDC_MINT, SOL_MINT, details.in_amount + 1).unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<OxediumError>(),
+        Some(OxediumError::InsufficientLiquidity { mint, .. }) if *mint == SOL_MINT
+    ));
+}
+
+#[test]
+fn test_fee_curve_shapes_imbalance_fee() {
+    // Drain half of the USDC vault, selling SOL for USDC pays a 50% deviation
+    let drained_usdc_vault = |account_map: &mut AccountMap| {
+        let data = &mut account_map.get_mut(&USDC_VAULT).unwrap().data;
+        data[VAULT_CURRENT_LIQUIDITY_OFFSET..VAULT_CURRENT_LIQUIDITY_OFFSET + 8]
+            .copy_from_slice(&1_353_119_841u64.to_le_bytes());
+    };
+    let params = quote_params(SOL_MINT, USDC_MINT, 1_000_000_000);
+    let quote_with_curve = |fee_curve: Option<Value>| {
+        let params_json = fee_curve.map(|fee_curve| json!({ "feeCurve": fee_curve }));
+        load_oxedium_amm_with_accounts(params_json, drained_usdc_vault)
+            .quote_detailed(&params)
+            .unwrap()
+    };
+
+    let default = quote_with_curve(None);
+    let quadratic = quote_with_curve(Some(json!({ "type": "quadratic" })));
+    let linear = quote_with_curve(Some(json!({ "type": "linear" })));
+    let cubic = quote_with_curve(Some(json!({ "type": "cubic" })));
+    let piecewise = quote_with_curve(Some(json!({
+        "type": "piecewise",
+        "points": [[0, 0], [10_000, 10_000]],
+    })));
+
+    assert_eq!(default, quadratic);
+    assert_eq!(quadratic.deviation_bps, 5_000);
+    assert_eq!(piecewise, linear);
+    assert!(linear.swap_fee_bps > quadratic.swap_fee_bps);
+    assert!(quadratic.swap_fee_bps > cubic.swap_fee_bps);
+    assert!(linear.out_amount < quadratic.out_amount);
+    assert!(quadratic.out_amount < cubic.out_amount);
+}
+
+#[test]
+fn test_invalid_fee_curve_params_are_rejected() {
+    let amm_test_accounts_snapshot = AmmTestAccountsSnapshot::load(OXEDIUM_POOL, None);
+    let mut keyed_account = amm_test_accounts_snapshot.get_keyed_account().unwrap();
+    keyed_account.params = Some(json!({
+        "feeCurve": { "type": "piecewise", "points": [] },
+    }));
+    let amm_context = AmmContext {
+        clock_ref: ClockRef::from(amm_test_accounts_snapshot.get_clock().unwrap()),
+    };
+    assert!(OxediumAmm::from_keyed_account(&keyed_account, &amm_context).is_err());
+}
+
+#[test]
+fn test_apply_swap_moves_vault_liquidity() {
+    let amm = load_oxedium_amm_with_accounts(None, |account_map| {
+        let data = &mut account_map.get_mut(&OXEDIUM_POOL).unwrap().data;
+        data[TREASURY_FEE_BPS_OFFSET..TREASURY_FEE_BPS_OFFSET + 8]
+            .copy_from_slice(&25u64.to_le_bytes());
+    });
+    let params = quote_params(SOL_MINT, USDC_MINT, 5_000_000_000);
+
+    let (details, swapped_amm) = amm.apply_swap(&params).unwrap();
+    let sol_vault = &swapped_amm.vaults[&SOL_MINT];
+    let usdc_vault = &swapped_amm.vaults[&USDC_MINT];
+    assert_eq!(
+        sol_vault.current_liquidity,
+        amm.vaults[&SOL_MINT].current_liquidity + 5_000_000_000
+    );
+    // Both fees stay in the output vault liquidity, they are accounted as yield
+    assert_eq!(
+        usdc_vault.current_liquidity,
+        amm.vaults[&USDC_MINT].current_liquidity - details.out_amount
+    );
+    assert_eq!(
+        usdc_vault.cumulative_yield_per_lp,
+        amm.vaults[&USDC_MINT].cumulative_yield_per_lp
+            + details.lp_fee_amount as u128 * SCALE
+                / amm.vaults[&USDC_MINT].initial_liquidity as u128
+    );
+    assert_eq!(
+        usdc_vault.protocol_yield,
+        amm.vaults[&USDC_MINT].protocol_yield + details.protocol_fee_amount
+    );
+    // The original AMM is left untouched
+    assert_eq!(amm.quote_detailed(&params).unwrap(), details);
+
+    // The same swap again pays the imbalance fee created by the first one
+    let (second_details, _) = swapped_amm.apply_swap(&params).unwrap();
+    assert!(second_details.deviation_bps > details.deviation_bps);
+    assert!(second_details.swap_fee_bps > details.swap_fee_bps);
+    assert!(second_details.out_amount < details.out_amount);
+}
+
+#[test]
+fn test_apply_swap_matches_program_vault_accounts() {
+    let amm_test_accounts_snapshot = AmmTestAccountsSnapshot::load(OXEDIUM_POOL, None);
+    let clock = amm_test_accounts_snapshot.get_clock().unwrap();
+    let keyed_account = amm_test_accounts_snapshot.get_keyed_account().unwrap();
+    let amm_context = AmmContext {
+        clock_ref: ClockRef::from(clock.clone()),
+    };
+    let mut amm = OxediumAmm::from_keyed_account(&keyed_account, &amm_context).unwrap();
+    let test_programs = load_test_programs(&amm, None);
+
+    let mut before_test_setup = |_: &dyn Amm, account_map: &mut AccountMap| {
+        refresh_pyth_price_updates(account_map, clock.unix_timestamp);
+        // Charge a protocol fee so that both fees are accounted
+        let data = &mut account_map.get_mut(&OXEDIUM_POOL).unwrap().data;
+        data[TREASURY_FEE_BPS_OFFSET..TREASURY_FEE_BPS_OFFSET + 8]
+            .copy_from_slice(&25u64.to_le_bytes());
+    };
+    let mut program_test = AmmTestHarness::load_program_test(
+        &amm_test_accounts_snapshot,
+        &test_programs,
+        &mut amm,
+        Some(&[SOL_MINT, USDC_MINT]),
+        Some(&mut before_test_setup),
+    );
+
+    // The second swap starts from the vaults written by the first one
+    for (input_mint, output_mint, amount) in [
+        (SOL_MINT, USDC_MINT, 25_000_000),
+        (USDC_MINT, SOL_MINT, 1_110_000),
+    ] {
+        let (details, swapped_amm) = amm
+            .apply_swap(&quote_params(input_mint, output_mint, amount))
+            .unwrap();
+        assert!(details.protocol_fee_amount > 0);
+        let (quote, simulated_swap) = program_test.quote_and_simulate_swap(
+            &amm,
+            &input_mint,
+            &output_mint,
+            SwapMode::ExactIn,
+            amount,
+            false,
+        );
+        assert_eq!(
+            quote.unwrap().out_amount,
+            simulated_swap.unwrap().out_amount
+        );
+
+        for (mint, vault_pda) in [(SOL_MINT, SOL_VAULT), (USDC_MINT, USDC_VAULT)] {
+            let vault_account = program_test.get_account(&vault_pda).unwrap();
+            let program_vault = Vault::deserialize(&mut &vault_account.data[8..]).unwrap();
+            let vault = &swapped_amm.vaults[&mint];
+            assert_eq!(program_vault.current_liquidity, vault.current_liquidity);
+            assert_eq!(
+                program_vault.cumulative_yield_per_lp,
+                vault.cumulative_yield_per_lp
+            );
+            assert_eq!(program_vault.protocol_yield, vault.protocol_yield);
+        }
+        amm = swapped_amm;
+    }
+}
+
+#[test]
+fn test_quote_rejects_unverified_or_mismatched_feed() {
+    let mut amm = load_oxedium_amm(None);
+    let sol_feed_id = amm.oracles[&SOL_ORACLE].price_message.feed_id;
+    assert_eq!(amm.feed_ids[&SOL_MINT], sol_feed_id);
+
+    amm.oracles.get_mut(&SOL_ORACLE).unwrap().verification_level =
+        VerificationLevel::Partial { num_signatures: 5 };
+    let error = quote(&amm, SOL_MINT, USDC_MINT, 25_000_000).unwrap_err();
+    assert_eq!(
+        error.downcast_ref::<OxediumError>(),
+        Some(&OxediumError::OracleNotFullyVerified { oracle: SOL_ORACLE })
+    );
+
+    let mut amm = load_oxedium_amm(None);
+    amm.feed_ids.insert(SOL_MINT, [1; 32]);
+    let error = quote(&amm, USDC_MINT, SOL_MINT, 1_110_000).unwrap_err();
+    assert_eq!(
+        error.downcast_ref::<OxediumError>(),
+        Some(&OxediumError::OracleFeedIdMismatch {
+            oracle: SOL_ORACLE,
+            mint: SOL_MINT,
+            expected: [1; 32],
+            actual: sol_feed_id,
+        })
+    );
+}
+
+#[test]
+fn test_update_records_mints_without_feed_id() {
+    let mut amm = load_oxedium_amm(None);
+    assert!(amm.mints_without_feed_id.is_empty());
+
+    amm.feed_ids.remove(&USDC_MINT);
+    let mut account_map = load_accounts_snapshot(&snapshot_directory_name(OXEDIUM_POOL, None));
+    refresh_pyth_price_updates(
+        &mut account_map,
+        amm.clock_ref.unix_timestamp.load(Ordering::Relaxed),
+    );
+    amm.update(&account_map).unwrap();
+    assert_eq!(amm.mints_without_feed_id, HashSet::from([USDC_MINT]));
+    // The oracle is still checked for verification and quoted
+    assert!(amm.stale_accounts.is_empty());
+    assert!(quote(&amm, SOL_MINT, USDC_MINT, 25_000_000).is_ok());
+}
+
+#[test]
+fn test_strict_update_rejects_mints_without_feed_id() {
+    let mut amm = load_oxedium_amm(Some(json!({ "updateMode": "strict" })));
+    let before = quote(&amm, SOL_MINT, USDC_MINT, 25_000_000).unwrap();
+
+    amm.feed_ids.remove(&USDC_MINT);
+    let mut account_map = load_accounts_snapshot(&snapshot_directory_name(OXEDIUM_POOL, None));
+    refresh_pyth_price_updates(
+        &mut account_map,
+        amm.clock_ref.unix_timestamp.load(Ordering::Relaxed),
+    );
+    let error = amm.update(&account_map).unwrap_err();
+    assert_eq!(
+        error.downcast_ref::<OxediumError>(),
+        Some(&OxediumError::MissingFeedId { mint: USDC_MINT })
+    );
+    assert!(amm.mints_without_feed_id.is_empty());
+    assert_eq!(
+        quote(&amm, SOL_MINT, USDC_MINT, 25_000_000)
+            .unwrap()
+            .out_amount,
+        before.out_amount
+    );
+}
+
+#[test]
+fn test_configured_vaults_need_feed_ids() {
+    let vaults = json!([SOL_VAULT.to_string(), USDC_VAULT.to_string()]);
+
+    // The default feed ids only go with the default vaults
+    let amm = load_oxedium_amm(Some(json!({ "vaults": vaults })));
+    assert_eq!(
+        amm.mints_without_feed_id,
+        HashSet::from([SOL_MINT, USDC_MINT])
+    );
+
+    let amm = load_oxedium_amm(Some(json!({
+        "vaults": vaults,
+        "feedIds": {
+            SOL_MINT.to_string(): "ef0d8b6fda2ceba41da15d4095d1da392a0d2f8ed0c6c7bc0f4cfac8c280b56d",
+            USDC_MINT.to_string(): "eaa020c61cc479712813461ce153894a96a6c00b21ed0cfc2798d1f9a9e9c94a"
+        }
+    })));
+    assert!(amm.mints_without_feed_id.is_empty());
+    assert!(amm.stale_accounts.is_empty());
+}
+
+#[test]
+fn test_update_rejects_mismatched_feed_id() {
+    // The SOL vault oracle is not the USDC feed
+    let amm = load_oxedium_amm(Some(json!({
+        "feedIds": {
+            SOL_MINT.to_string(): "eaa020c61cc479712813461ce153894a96a6c00b21ed0cfc2798d1f9a9e9c94a"
+        }
+    })));
+    assert!(amm.stale_accounts.contains(&SOL_ORACLE));
+    let error = quote(&amm, SOL_MINT, USDC_MINT, 25_000_000).unwrap_err();
+    assert_eq!(
+        error.downcast_ref::<OxediumError>(),
+        Some(&OxediumError::OracleNotFound { oracle: SOL_ORACLE })
+    );
+}
+
+#[test]
+fn test_strict_update_rejects_oracle_not_owned_by_pyth_receiver() {
+    let mut amm = load_oxedium_amm(Some(json!({ "updateMode": "strict" })));
+    let mut account_map = load_accounts_snapshot(&snapshot_directory_name(OXEDIUM_POOL, None));
+    refresh_pyth_price_updates(
+        &mut account_map,
+        amm.clock_ref.unix_timestamp.load(Ordering::Relaxed),
+    );
+    let owner = Pubkey::new_unique();
+    account_map.get_mut(&SOL_ORACLE).unwrap().owner = owner;
+
+    let error = amm.update(&account_map).unwrap_err();
+    assert_eq!(
+        error.downcast_ref::<OxediumError>(),
+        Some(&OxediumError::InvalidAccount {
+            address: SOL_ORACLE,
+            reason: OxediumError::InvalidOracleOwner {
+                oracle: SOL_ORACLE,
+                owner,
+            }
+            .to_string(),
+        })
+    );
+}
+
+#[test]
+fn test_update_rejects_vault_not_owned_by_program_or_of_another_type() {
+    let mut amm = load_oxedium_amm(Some(json!({ "updateMode": "strict" })));
+    let mut account_map = load_accounts_snapshot(&snapshot_directory_name(OXEDIUM_POOL, None));
+    refresh_pyth_price_updates(
+        &mut account_map,
+        amm.clock_ref.unix_timestamp.load(Ordering::Relaxed),
+    );
+    let owner = Pubkey::new_unique();
+    account_map.get_mut(&SOL_VAULT).unwrap().owner = owner;
+
+    let error = amm.update(&account_map).unwrap_err();
+    assert_eq!(
+        error.downcast_ref::<OxediumError>(),
+        Some(&OxediumError::InvalidAccount {
+            address: SOL_VAULT,
+            reason: OxediumError::InvalidAccountOwner {
+                address: SOL_VAULT,
+                owner,
+                program_id: OXEDIUM_PROGRAM_ID,
+            }
+            .to_string(),
+        })
+    );
+
+    // A program account of another type is kept stale in lenient mode
+    let mut amm = load_oxedium_amm(None);
+    let sol_vault = account_map.get_mut(&SOL_VAULT).unwrap();
+    sol_vault.owner = OXEDIUM_PROGRAM_ID;
+    sol_vault.data[..8].copy_from_slice(&STAKER_DISCRIMINATOR);
+    amm.update(&account_map).unwrap();
+    assert!(amm.stale_accounts.contains(&SOL_VAULT));
+    assert!(quote(&amm, SOL_MINT, USDC_MINT, 25_000_000).is_ok());
+
+    let mut amm = load_oxedium_amm(Some(json!({ "updateMode": "strict" })));
+    let error = amm.update(&account_map).unwrap_err();
+    assert_eq!(
+        error.downcast_ref::<OxediumError>(),
+        Some(&OxediumError::InvalidAccount {
+            address: SOL_VAULT,
+            reason: OxediumError::InvalidDiscriminator {
+                address: SOL_VAULT,
+                expected: VAULT_DISCRIMINATOR,
+                actual: STAKER_DISCRIMINATOR,
+            }
+            .to_string(),
+        })
+    );
+}
+
+#[test]
+fn test_quote_rejects_non_positive_price() {
+    for price in [0, -1, i64::MIN] {
+        let mut amm = load_oxedium_amm(None);
+        amm.oracles
+            .get_mut(&SOL_ORACLE)
+            .unwrap()
+            .price_message
+            .price = price;
+        for (input_mint, output_mint, amount) in [
+            (SOL_MINT, USDC_MINT, 25_000_000),
+            (USDC_MINT, SOL_MINT, 1_110_000),
+        ] {
+            let error = quote(&amm, input_mint, output_mint, amount).unwrap_err();
+            assert_eq!(
+                error.downcast_ref::<OxediumError>(),
+                Some(&OxediumError::NonPositiveOraclePrice { price })
+            );
+        }
+    }
+}
+
+#[test]
+fn test_update_rejects_non_positive_price() {
+    let mut amm = load_oxedium_amm(Some(json!({ "updateMode": "strict" })));
+    let mut account_map = load_accounts_snapshot(&snapshot_directory_name(OXEDIUM_POOL, None));
+    refresh_pyth_price_updates(
+        &mut account_map,
+        amm.clock_ref.unix_timestamp.load(Ordering::Relaxed),
+    );
+    account_map.get_mut(&SOL_ORACLE).unwrap().data[ORACLE_PRICE_OFFSET..ORACLE_PRICE_OFFSET + 8]
+        .copy_from_slice(&(-5i64).to_le_bytes());
+
+    let error = amm.update(&account_map).unwrap_err();
+    assert_eq!(
+        error.downcast_ref::<OxediumError>(),
+        Some(&OxediumError::InvalidAccount {
+            address: SOL_ORACLE,
+            reason: OxediumError::NonPositiveOraclePrice { price: -5 }.to_string(),
+        })
+    );
+}
+
+/// Registers a program id for the lifetime of the guard, so that no other test sees it
+struct RegisteredProgramId(Pubkey);
+
+impl RegisteredProgramId {
+    fn new(label: &'static str) -> Self {
+        let program_id = Pubkey::new_unique();
+        OxediumAmm::register_program_id(program_id, label);
+        Self(program_id)
+    }
+}
+
+impl Drop for RegisteredProgramId {
+    fn drop(&mut self) {
+        unregister_program_id(&self.0);
+    }
+}
+
+#[test]
+fn test_staging_program_id_flows_through_pdas() {
+    let registered_program_id = RegisteredProgramId::new("Oxedium Staging");
+    let staging_program_id = registered_program_id.0;
+    assert_eq!(
+        program_id_to_label().get(&staging_program_id.to_string()),
+        Some(&"Oxedium Staging".to_string())
+    );
+
+    let amm_test_accounts_snapshot = AmmTestAccountsSnapshot::load(OXEDIUM_POOL, None);
+    let amm_context = AmmContext {
+        clock_ref: ClockRef::from(amm_test_accounts_snapshot.get_clock().unwrap()),
+    };
+    let mut keyed_account = amm_test_accounts_snapshot.get_keyed_account().unwrap();
+    keyed_account.account.owner = staging_program_id;
+
+    let amm = amm_factory(&keyed_account, &amm_context).unwrap().unwrap();
+    assert_eq!(amm.program_id(), staging_program_id);
+    assert_eq!(amm.label(), "Oxedium Staging");
+    let staging_treasury_pda = find_treasury_pda(&staging_program_id);
+    assert_eq!(
+        amm.get_accounts_to_update(),
+        vec![
+            staging_treasury_pda,
+            find_vault_pda(&SOL_MINT, &staging_program_id),
+            find_vault_pda(&USDC_MINT, &staging_program_id),
+        ]
+    );
+    assert_ne!(staging_treasury_pda, OXEDIUM_POOL);
+
+    // Params take precedence over the keyed account owner
+    keyed_account.params = Some(json!({ "programId": OXEDIUM_PROGRAM_ID.to_string() }));
+    let amm = OxediumAmm::from_keyed_account(&keyed_account, &amm_context).unwrap();
+    assert_eq!(amm.program_id, OXEDIUM_PROGRAM_ID);
+    assert_eq!(amm.label, "Oxedium");
+    assert_eq!(amm.treasury_pda, OXEDIUM_POOL);
+
+    drop(registered_program_id);
+    keyed_account.params = None;
+    assert!(amm_factory(&keyed_account, &amm_context).unwrap().is_none());
+    assert!(!program_id_to_label().contains_key(&staging_program_id.to_string()));
+}
+
+#[test]
+fn test_quote_errors_are_typed() {
+    let unknown_mint = Pubkey::new_unique();
+    let error = quote(&load_oxedium_amm(None), unknown_mint, USDC_MINT, 1_000_000).unwrap_err();
+    assert_eq!(
+        error.downcast_ref::<OxediumError>(),
+        Some(&OxediumError::VaultNotFound { mint: unknown_mint })
+    );
+
+    let current_liquidity = 1_000_000u64;
+    let amm = load_oxedium_amm_with_accounts(None, |account_map| {
+        let data = &mut account_map.get_mut(&USDC_VAULT).unwrap().data;
+        data[VAULT_CURRENT_LIQUIDITY_OFFSET..VAULT_CURRENT_LIQUIDITY_OFFSET + 8]
+            .copy_from_slice(&current_liquidity.to_le_bytes());
+    });
+    let error = quote(&amm, SOL_MINT, USDC_MINT, 25_000_000).unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<OxediumError>(),
+        Some(OxediumError::InsufficientLiquidity {
+            mint: USDC_MINT,
+            available: 1_000_000,
+            required,
+        }) if *required > current_liquidity
+    ));
+
+    let error = amm
+        .quote(&QuoteParams {
+            amount: 0,
+            input_mint: SOL_MINT,
+            output_mint: USDC_MINT,
+            swap_mode: SwapMode::ExactOut,
+        })
+        .unwrap_err();
+    assert_eq!(
+        error.downcast_ref::<OxediumError>(),
+        Some(&OxediumError::ZeroExactOutAmount)
+    );
+}
+
+/// A Token-2022 mint carrying a `TransferFeeConfig` extension with an uncapped fee
+fn token_2022_mint_with_transfer_fee(decimals: u8, transfer_fee_basis_points: u16) -> Account {
+    let space =
+        ExtensionType::try_calculate_account_len::<Mint>(&[ExtensionType::TransferFeeConfig])
+            .unwrap();
+    let mut data = vec![0; space];
+    let mut state = StateWithExtensionsMut::<Mint>::unpack_uninitialized(&mut data).unwrap();
+    let transfer_fee = TransferFee {
+        epoch: 0.into(),
+        maximum_fee: u64::MAX.into(),
+        transfer_fee_basis_points: transfer_fee_basis_points.into(),
+    };
+    let transfer_fee_config = state.init_extension::<TransferFeeConfig>(true).unwrap();
+    transfer_fee_config.older_transfer_fee = transfer_fee;
+    transfer_fee_config.newer_transfer_fee = transfer_fee;
+    state.base = Mint {
+        decimals,
+        is_initialized: true,
+        ..Default::default()
+    };
+    state.pack_base();
+    state.init_account_type().unwrap();
+
+    Account {
+        lamports: 1_000_000_000,
+        data,
+        owner: spl_token_2022::ID,
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+#[test]
+fn test_token_2022_vault_mints_are_rejected() {
+    let pyusd_mint = *TOKEN2022_MINT_TO_IN_AMOUNT.keys().next().unwrap();
+    // The USDC vault is pointed at PYUSD, which carries a zero transfer fee
+    let amm = load_oxedium_amm_with_accounts(None, |account_map| {
+        account_map.insert(pyusd_mint, token_2022_mint_with_transfer_fee(6, 0));
+        let data = &mut account_map.get_mut(&USDC_VAULT).unwrap().data;
+        data[VAULT_TOKEN_MINT_OFFSET..VAULT_TOKEN_MINT_OFFSET + 32]
+            .copy_from_slice(pyusd_mint.as_ref());
+    });
+    assert_eq!(amm.mints[&pyusd_mint].token_program, spl_token_2022::ID);
+    assert_eq!(amm.get_reserve_mints(), vec![SOL_MINT]);
+
+    // oxedium_swap only transfers SPL Token mints, the pair is not quoted either
+    let expected_error = OxediumError::UnsupportedTokenProgram {
+        mint: pyusd_mint,
+        token_program: spl_token_2022::ID,
+    };
+    for (input_mint, output_mint) in [(SOL_MINT, pyusd_mint), (pyusd_mint, SOL_MINT)] {
+        for swap_mode in [SwapMode::ExactIn, SwapMode::ExactOut] {
+            let error = amm
+                .quote(&QuoteParams {
+                    amount: 1_000_000,
+                    input_mint,
+                    output_mint,
+                    swap_mode,
+                })
+                .unwrap_err();
+            assert_eq!(error.downcast_ref::<OxediumError>(), Some(&expected_error));
+        }
+        let error = swap_and_account_metas(&amm, input_mint, output_mint).unwrap_err();
+        assert_eq!(error.downcast_ref::<OxediumError>(), Some(&expected_error));
+    }
+}
+
+#[test]
+fn test_staker_position_of_tracked_vault() {
+    // Yield per LP token accrued by the USDC vault, the snapshot vaults have none yet
+    let usdc_cumulative_yield_per_lp = 3 * SCALE / 2;
+    let amm = load_oxedium_amm_with_accounts(None, |account_map| {
+        let data = &mut account_map.get_mut(&USDC_VAULT).unwrap().data;
+        data[VAULT_CUMULATIVE_YIELD_PER_LP_OFFSET..VAULT_CUMULATIVE_YIELD_PER_LP_OFFSET + 16]
+            .copy_from_slice(&usdc_cumulative_yield_per_lp.to_le_bytes());
+    });
+    assert_eq!(
+        amm.vaults[&USDC_MINT].cumulative_yield_per_lp,
+        usdc_cumulative_yield_per_lp
+    );
+    let owner = Pubkey::new_unique();
+    let sol_staker_address = find_staker_pda(&SOL_VAULT, &owner, &amm.program_id);
+    let usdc_staker_address = find_staker_pda(&USDC_VAULT, &owner, &amm.program_id);
+    let staker = Staker {
+        owner,
+        vault_mint: SOL_MINT,
+        last_cumulative_yield: 0,
+        pending_claim: 1_000,
+    };
+    let staker_account = |staker: &Staker, owner: Pubkey| {
+        let mut data = STAKER_DISCRIMINATOR.to_vec();
+        data.extend(borsh::to_vec(staker).unwrap());
+        Account {
+            lamports: 1_000_000,
+            data,
+            owner,
+            executable: false,
+            rent_epoch: 0,
+        }
+    };
+
+    // Only the settled yield is claimable when the vault accrued nothing since the checkpoint,
+    // LP tokens are redeemed 1:1 while the vault holds its initial liquidity
+    let position = amm
+        .staker_position(
+            &sol_staker_address,
+            &staker_account(&staker, amm.program_id),
+            5_000_000_000,
+        )
+        .unwrap();
+    assert_eq!(
+        position,
+        StakerPosition {
+            lp_balance: 5_000_000_000,
+            lp_value: 5_000_000_000,
+            claimable_yield: 1_000,
+        }
+    );
+
+    // One token of yield per LP token accrued since the checkpoint adds to the settled yield
+    let staker = Staker {
+        vault_mint: USDC_MINT,
+        last_cumulative_yield: SCALE / 2,
+        ..staker
+    };
+    let position = amm
+        .staker_position(
+            &usdc_staker_address,
+            &staker_account(&staker, amm.program_id),
+            500,
+        )
+        .unwrap();
+    assert_eq!(position.claimable_yield, 1_500);
+
+    let error = amm
+        .staker_position(
+            &usdc_staker_address,
+            &staker_account(&staker, Pubkey::new_unique()),
+            500,
+        )
+        .unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<OxediumError>(),
+        Some(OxediumError::InvalidAccount { address, .. }) if *address == usdc_staker_address
+    ));
+
+    // A staker of the USDC vault cannot be valued at the address of another PDA
+    let error = amm
+        .staker_position(
+            &sol_staker_address,
+            &staker_account(&staker, amm.program_id),
+            500,
+        )
+        .unwrap_err();
+    assert_eq!(
+        error.downcast_ref::<OxediumError>(),
+        Some(&OxediumError::StakerAddressMismatch {
+            address: sol_staker_address,
+            expected: usdc_staker_address,
+        })
+    );
+
+    let untracked_mint = Pubkey::new_unique();
+    let staker = Staker {
+        vault_mint: untracked_mint,
+        ..staker
+    };
+    let error = amm
+        .staker_position(
+            &usdc_staker_address,
+            &staker_account(&staker, amm.program_id),
+            500,
+        )
+        .unwrap_err();
+    assert_eq!(
+        error.downcast_ref::<OxediumError>(),
+        Some(&OxediumError::StakerVaultNotFound {
+            mint: untracked_mint
+        })
+    );
+}
@@ -0,0 +1,468 @@
+mod common;
+
+use anchor_lang::Discriminator;
+use common::{
+    ORACLE_EXPONENT_OFFSET, ORACLE_PRICE_OFFSET, TOKEN_ACCOUNT_AMOUNT_OFFSET,
+    TREASURY_FEE_BPS_OFFSET, VAULT_BASE_FEE_OFFSET, VAULT_CURRENT_LIQUIDITY_OFFSET,
+    VAULT_INITIAL_LIQUIDITY_OFFSET, VAULT_MAX_LIQUIDITY_OFFSET,
+};
+use jupiter_amm_interface::{AccountMap, AmmContext, ClockRef, QuoteParams, SwapMode, SwapParams};
+use jupiter_core::{
+    amm::Amm,
+    amms::{
+        errors::OxediumError,
+        oxedium_amm::{find_treasury_pda, OxediumAmm},
+        test_harness::{AmmTestHarness, AmmTestHarnessProgramTest},
+    },
+    constants::USDC_MINT,
+    test_harness::{load_test_programs, refresh_pyth_price_updates, AmmTestAccountsSnapshot},
+};
+use program_interfaces::jupiter_dex_interfaces::client::args::OxediumSwap;
+use proptest::prelude::*;
+use proptest::test_runner::FileFailurePersistence;
+use solana_sdk::compute_budget::ComputeBudgetInstruction;
+use solana_sdk::instruction::{AccountMeta, Instruction, InstructionError};
+use solana_sdk::pubkey;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::transaction::TransactionError;
+use spl_associated_token_account::get_associated_token_address_with_program_id;
+
+const OXEDIUM_POOL: Pubkey = pubkey!("DZzt6k2QN77Khj4hYBZFyJVjzuV3KxkSqjMFaUvQoxz1");
+const JUPITER_PROGRAM_ID: Pubkey = pubkey!("JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4");
+const SOL_MINT: Pubkey = spl_token::native_mint::ID;
+const SOL_ORACLE: Pubkey = pubkey!("7UVimffxr9ow1uXYxsr4LHAcV58mLzhmwaeKvJ1pjLiE");
+const USDC_ORACLE: Pubkey = pubkey!("Dpw1EAVrSB1ibxiDQyTAW6Zip3J4Btk2x4SgApQCeFbX");
+const SOL_VAULT: Pubkey = pubkey!("6unq3WF6nRLpAqfYUAcLtXKr2tQnpAmoEHt7QGHBSkHC");
+const USDC_VAULT: Pubkey = pubkey!("DQc8SkUkRvp4bAyyCcLtX5Pgwb9gzioMzEVpzPxeZHbN");
+
+/// Treasury token balance backing any fuzzed vault liquidity
+const TREASURY_BALANCE: u64 = 1_000_000_000_000_000;
+
+/// Shrunk failing cases are persisted next to the other fixtures and replayed first
+const FUZZ_REGRESSIONS: &str = "tests/fixtures/oxedium_fuzz.proptest-regressions";
+
+#[derive(Clone, Debug)]
+struct VaultCase {
+    base_fee_bps: u64,
+    /// Initial liquidity in percent of the snapshotted one, zero included
+    initial_liquidity_pct: u64,
+    /// Current liquidity in percent of the fuzzed initial liquidity, capped at the max liquidity
+    liquidity_pct: u64,
+    /// Max liquidity in percent of the snapshotted one
+    max_liquidity_pct: u64,
+    /// Oracle price in percent of the snapshotted price
+    price_pct: u64,
+    /// Added to the oracle exponent, the same price value is worth `10^exponent_shift` more
+    exponent_shift: i32,
+}
+
+#[derive(Clone, Debug)]
+struct FuzzCase {
+    sol: VaultCase,
+    usdc: VaultCase,
+    protocol_fee_bps: u64,
+    swap_mode: SwapMode,
+    source_mint: Pubkey,
+    destination_mint: Pubkey,
+    /// Input amount for `ExactIn`, requested output amount for `ExactOut`
+    amount: u64,
+}
+
+/// Fees in basis points, mostly realistic ones with the odd one near or above 100%
+fn fee_bps() -> impl Strategy<Value = u64> {
+    prop_oneof![3 => 0..=100u64, 1 => 0..=10_000u64]
+}
+
+fn vault_case(price_pct: std::ops::RangeInclusive<u64>) -> impl Strategy<Value = VaultCase> {
+    (
+        fee_bps(),
+        prop_oneof![1 => Just(0u64), 9 => 1..=1_000u64],
+        0..=200u64,
+        0..=200u64,
+        price_pct,
+        -2..=2i32,
+    )
+        .prop_map(
+            |(
+                base_fee_bps,
+                initial_liquidity_pct,
+                liquidity_pct,
+                max_liquidity_pct,
+                price_pct,
+                exponent_shift,
+            )| VaultCase {
+                base_fee_bps,
+                initial_liquidity_pct,
+                liquidity_pct,
+                max_liquidity_pct,
+                price_pct,
+                exponent_shift,
+            },
+        )
+}
+
+fn fuzz_case() -> impl Strategy<Value = FuzzCase> {
+    // Inputs stay within the balances the harness funds the user with, requested outputs
+    // within what those balances buy at snapshot prices
+    let swap = prop_oneof![
+        (1..=2_500_000_000u64).prop_map(|amount| (SwapMode::ExactIn, SOL_MINT, USDC_MINT, amount)),
+        (1..=111_000_000u64).prop_map(|amount| (SwapMode::ExactIn, USDC_MINT, SOL_MINT, amount)),
+        (1..=300_000_000u64).prop_map(|amount| (SwapMode::ExactOut, SOL_MINT, USDC_MINT, amount)),
+        (1..=700_000_000u64).prop_map(|amount| (SwapMode::ExactOut, USDC_MINT, SOL_MINT, amount)),
+    ];
+    (vault_case(1..=1_000), vault_case(50..=150), fee_bps(), swap).prop_map(
+        |(sol, usdc, protocol_fee_bps, (swap_mode, source_mint, destination_mint, amount))| {
+            FuzzCase {
+                sol,
+                usdc,
+                protocol_fee_bps,
+                swap_mode,
+                source_mint,
+                destination_mint,
+                amount,
+            }
+        },
+    )
+}
+
+/// Failure reasons shared by the quote and the program, to check both fail for the same one
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum FailureKind {
+    VaultInactive,
+    VaultCapacityExceeded,
+    InsufficientLiquidity,
+    FeeTooHigh,
+    StaleOracle,
+    Slippage,
+    NoInitialLiquidity,
+    ArithmeticError,
+    Other(String),
+}
+
+impl FailureKind {
+    fn from_quote_error(error: &anyhow::Error) -> Self {
+        match error.downcast_ref::<OxediumError>() {
+            Some(OxediumError::VaultInactive { .. }) => Self::VaultInactive,
+            Some(OxediumError::VaultCapacityExceeded { .. }) => Self::VaultCapacityExceeded,
+            Some(OxediumError::InsufficientLiquidity { .. }) => Self::InsufficientLiquidity,
+            Some(OxediumError::TotalFeeTooHigh { .. }) => Self::FeeTooHigh,
+            Some(OxediumError::StaleOracle { .. }) => Self::StaleOracle,
+            Some(OxediumError::NoInitialLiquidity { .. }) => Self::NoInitialLiquidity,
+            Some(OxediumError::MathOverflow(_) | OxediumError::FeeOverflow) => {
+                Self::ArithmeticError
+            }
+            _ => Self::Other(error.to_string()),
+        }
+    }
+
+    /// Custom error codes are `6000` plus the index in the error enum of oxedium.so
+    fn from_program_error(error: &TransactionError) -> Self {
+        match error {
+            TransactionError::InstructionError(_, InstructionError::Custom(code)) => match code {
+                6012 => Self::InsufficientLiquidity,
+                6013 | 6014 => Self::VaultInactive,
+                6010 => Self::Slippage,
+                6022 => Self::StaleOracle,
+                6024 => Self::FeeTooHigh,
+                6025 => Self::VaultCapacityExceeded,
+                6007 | 6008 | 6019..=6021 | 6023 => Self::ArithmeticError,
+                _ => Self::Other(format!("{error:?}")),
+            },
+            // The only unchecked division of the program is the yield per LP, it panics on
+            // an output vault without initial liquidity
+            TransactionError::InstructionError(_, InstructionError::ProgramFailedToComplete) => {
+                Self::NoInitialLiquidity
+            }
+            _ => Self::Other(format!("{error:?}")),
+        }
+    }
+
+    /// Failures that depend on the swapped amount rather than on the vault and oracle state
+    fn is_amount_dependent(&self) -> bool {
+        matches!(
+            self,
+            Self::VaultCapacityExceeded
+                | Self::InsufficientLiquidity
+                | Self::Slippage
+                | Self::ArithmeticError
+        )
+    }
+}
+
+fn read_u64(account_map: &AccountMap, address: &Pubkey, offset: usize) -> u64 {
+    let data = &account_map[address].data;
+    u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap())
+}
+
+fn read_i32(account_map: &AccountMap, address: &Pubkey, offset: usize) -> i32 {
+    let data = &account_map[address].data;
+    i32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+fn write_u64(account_map: &mut AccountMap, address: &Pubkey, offset: usize, value: u64) {
+    let data = &mut account_map.get_mut(address).unwrap().data;
+    data[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+}
+
+fn write_i64(account_map: &mut AccountMap, address: &Pubkey, offset: usize, value: i64) {
+    let data = &mut account_map.get_mut(address).unwrap().data;
+    data[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+}
+
+fn write_i32(account_map: &mut AccountMap, address: &Pubkey, offset: usize, value: i32) {
+    let data = &mut account_map.get_mut(address).unwrap().data;
+    data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+/// Writes the fuzzed vault, treasury and oracle state into the snapshot seen by both the amm
+/// and LiteSVM
+fn apply_fuzz_case(case: &FuzzCase, account_map: &mut AccountMap, unix_timestamp: i64) {
+    refresh_pyth_price_updates(account_map, unix_timestamp);
+
+    for (vault, oracle, vault_case) in [
+        (SOL_VAULT, SOL_ORACLE, &case.sol),
+        (USDC_VAULT, USDC_ORACLE, &case.usdc),
+    ] {
+        let initial_liquidity = read_u64(account_map, &vault, VAULT_INITIAL_LIQUIDITY_OFFSET)
+            * vault_case.initial_liquidity_pct
+            / 100;
+        let max_liquidity = read_u64(account_map, &vault, VAULT_MAX_LIQUIDITY_OFFSET)
+            * vault_case.max_liquidity_pct
+            / 100;
+        let current_liquidity =
+            (initial_liquidity * vault_case.liquidity_pct / 100).min(max_liquidity);
+        write_u64(
+            account_map,
+            &vault,
+            VAULT_BASE_FEE_OFFSET,
+            vault_case.base_fee_bps,
+        );
+        write_u64(
+            account_map,
+            &vault,
+            VAULT_INITIAL_LIQUIDITY_OFFSET,
+            initial_liquidity,
+        );
+        write_u64(
+            account_map,
+            &vault,
+            VAULT_CURRENT_LIQUIDITY_OFFSET,
+            current_liquidity,
+        );
+        write_u64(
+            account_map,
+            &vault,
+            VAULT_MAX_LIQUIDITY_OFFSET,
+            max_liquidity,
+        );
+
+        let price = read_u64(account_map, &oracle, ORACLE_PRICE_OFFSET) as i64;
+        let exponent = read_i32(account_map, &oracle, ORACLE_EXPONENT_OFFSET);
+        write_i64(
+            account_map,
+            &oracle,
+            ORACLE_PRICE_OFFSET,
+            price * vault_case.price_pct as i64 / 100,
+        );
+        write_i32(
+            account_map,
+            &oracle,
+            ORACLE_EXPONENT_OFFSET,
+            exponent + vault_case.exponent_shift,
+        );
+    }
+
+    // The program pays out of the treasury token accounts, keep them above any vault liquidity
+    let treasury_pda = find_treasury_pda(&account_map[&OXEDIUM_POOL].owner);
+    write_u64(
+        account_map,
+        &treasury_pda,
+        TREASURY_FEE_BPS_OFFSET,
+        case.protocol_fee_bps,
+    );
+    for mint in [SOL_MINT, USDC_MINT] {
+        let treasury_ata =
+            get_associated_token_address_with_program_id(&treasury_pda, &mint, &spl_token::ID);
+        write_u64(
+            account_map,
+            &treasury_ata,
+            TOKEN_ACCOUNT_AMOUNT_OFFSET,
+            TREASURY_BALANCE,
+        );
+    }
+}
+
+fn token_balance(program_test: &AmmTestHarnessProgramTest, address: &Pubkey) -> u64 {
+    let data = program_test.get_account(address).unwrap().data;
+    u64::from_le_bytes(
+        data[TOKEN_ACCOUNT_AMOUNT_OFFSET..TOKEN_ACCOUNT_AMOUNT_OFFSET + 8]
+            .try_into()
+            .unwrap(),
+    )
+}
+
+/// `oxedium_swap` called directly by the test user, outside of a Jupiter route
+fn oxedium_swap_instruction(
+    amm: &OxediumAmm,
+    program_test: &AmmTestHarnessProgramTest,
+    case: &FuzzCase,
+    amount_in: u64,
+    minimum_amount_out: u64,
+) -> Instruction {
+    let user = program_test.get_user();
+    let mut account_metas = amm
+        .get_swap_and_account_metas(&SwapParams {
+            swap_mode: SwapMode::ExactIn,
+            source_mint: case.source_mint,
+            destination_mint: case.destination_mint,
+            source_token_account: program_test.get_user_ata(&case.source_mint),
+            destination_token_account: program_test.get_user_ata(&case.destination_mint),
+            token_transfer_authority: user,
+            quote_mint_to_referrer: None,
+            in_amount: amount_in,
+            out_amount: minimum_amount_out,
+            jupiter_program_id: &JUPITER_PROGRAM_ID,
+            missing_dynamic_accounts_as_default: false,
+        })
+        .unwrap()
+        .account_metas;
+    // The user signs for the source tokens instead of the aggregator
+    account_metas[0] = AccountMeta::new(user, true);
+
+    let mut data = OxediumSwap::DISCRIMINATOR.to_vec();
+    data.extend_from_slice(&amount_in.to_le_bytes());
+    data.extend_from_slice(&minimum_amount_out.to_le_bytes());
+    Instruction {
+        program_id: amm.program_id,
+        accounts: account_metas,
+        data,
+    }
+}
+
+fn fuzz_config() -> ProptestConfig {
+    let mut config =
+        ProptestConfig::with_failure_persistence(FileFailurePersistence::Direct(FUZZ_REGRESSIONS));
+    // Every case sets up LiteSVM, keep the default run short unless PROPTEST_CASES asks for more
+    if std::env::var("PROPTEST_CASES").is_err() {
+        config.cases = 32;
+    }
+    config
+}
+
+proptest! {
+    #![proptest_config(fuzz_config())]
+
+    /// Quotes must match `oxedium_swap` executed by the on-chain program on the same state,
+    /// including agreeing on why a swap fails
+    #[test]
+    fn test_oxedium_quote_matches_program(case in fuzz_case()) {
+        let amm_test_accounts_snapshot = AmmTestAccountsSnapshot::load(OXEDIUM_POOL, None);
+        let clock = amm_test_accounts_snapshot.get_clock().unwrap();
+        let keyed_account = amm_test_accounts_snapshot.get_keyed_account().unwrap();
+        let amm_context = AmmContext {
+            clock_ref: ClockRef::from(clock.clone()),
+        };
+        let mut amm = OxediumAmm::from_keyed_account(&keyed_account, &amm_context).unwrap();
+        let test_programs = load_test_programs(&amm, None);
+
+        let mut before_test_setup = |_: &dyn Amm, account_map: &mut AccountMap| {
+            apply_fuzz_case(&case, account_map, clock.unix_timestamp)
+        };
+        let mut program_test = AmmTestHarness::load_program_test(
+            &amm_test_accounts_snapshot,
+            &test_programs,
+            &mut amm,
+            Some(&[case.source_mint, case.destination_mint]),
+            Some(&mut before_test_setup),
+        );
+
+        let quote = amm.quote(&QuoteParams {
+            amount: case.amount,
+            input_mint: case.source_mint,
+            output_mint: case.destination_mint,
+            swap_mode: case.swap_mode,
+        });
+        let user_source_token_account = program_test.get_user_ata(&case.source_mint);
+        let user_destination_token_account = program_test.get_user_ata(&case.destination_mint);
+        let user_balance = token_balance(&program_test, &user_source_token_account);
+
+        // ExactOut runs the quoted input with the requested output as minimum, or the whole
+        // user balance when no input is quoted
+        let (amount_in, minimum_amount_out) = match (case.swap_mode, &quote) {
+            (SwapMode::ExactIn, _) => (case.amount, 0),
+            (SwapMode::ExactOut, Ok(quote)) => {
+                prop_assume!(quote.in_amount <= user_balance);
+                (quote.in_amount, case.amount)
+            }
+            (SwapMode::ExactOut, Err(_)) => (user_balance, case.amount),
+        };
+        let instructions = [
+            ComputeBudgetInstruction::set_compute_unit_limit(1_400_000),
+            oxedium_swap_instruction(&amm, &program_test, &case, amount_in, minimum_amount_out),
+        ];
+        let executed_swap = program_test.execute_user_swap(
+            &instructions,
+            &user_source_token_account,
+            &user_destination_token_account,
+        );
+
+        match (quote, executed_swap) {
+            (Ok(quote), Ok(executed_swap)) => {
+                prop_assert_eq!(quote.in_amount, executed_swap.in_amount);
+                prop_assert_eq!(quote.out_amount, executed_swap.out_amount);
+                if matches!(case.swap_mode, SwapMode::ExactOut) {
+                    prop_assert!(executed_swap.out_amount >= case.amount);
+                }
+            }
+            (Err(quote_error), Err(program_error)) => {
+                let quote_failure = FailureKind::from_quote_error(&quote_error);
+                let program_failure = FailureKind::from_program_error(&program_error);
+                let exact_out_not_found = matches!(
+                    quote_error.downcast_ref::<OxediumError>(),
+                    Some(OxediumError::ExactOutNotFound { .. })
+                );
+                let output_has_initial_liquidity = if case.destination_mint == SOL_MINT {
+                    case.sol.initial_liquidity_pct > 0
+                } else {
+                    case.usdc.initial_liquidity_pct > 0
+                };
+                prop_assert!(
+                    program_failure != FailureKind::NoInitialLiquidity
+                        || !output_has_initial_liquidity,
+                    "the program failed on initial liquidity with {:?}",
+                    program_error
+                );
+                if matches!(case.swap_mode, SwapMode::ExactOut)
+                    && (exact_out_not_found
+                        || quote_failure.is_amount_dependent()
+                        || quote_failure == FailureKind::NoInitialLiquidity)
+                {
+                    // The program runs on another input than the ones the quote tried, it only
+                    // has to fail on the amount too, or reach the yield per LP of an output
+                    // vault without initial liquidity
+                    prop_assert!(
+                        program_failure.is_amount_dependent()
+                            || program_failure == FailureKind::NoInitialLiquidity,
+                        "quote failed with {} but the program with {:?}",
+                        quote_error,
+                        program_error
+                    );
+                } else {
+                    prop_assert_eq!(
+                        quote_failure,
+                        program_failure,
+                        "quote failed with {} but the program with {:?}",
+                        quote_error,
+                        program_error
+                    );
+                }
+            }
+            (Ok(quote), Err(error)) => {
+                prop_assert!(false, "quoted {:?} but the program failed: {:?}", quote, error);
+            }
+            (Err(error), Ok(executed_swap)) => {
+                prop_assert!(false, "quote failed with {} but the program swapped {:?}", error, executed_swap);
+            }
+        }
+    }
+}